@@ -1,3 +1,7 @@
 fn main() {
+    // the pure-rust feature replaces everything the C code provides
+    if std::env::var_os("CARGO_FEATURE_PURE_RUST").is_some() {
+        return;
+    }
     cc::Build::new().file(r#"c/stb.c"#).compile("stb");
 }
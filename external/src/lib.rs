@@ -1,2 +1,3 @@
+mod png;
 pub mod squirrel_noise;
 pub mod stb;
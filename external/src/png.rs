@@ -0,0 +1,622 @@
+//! PNG encoder in plain Rust, used for 16-bit images and for builds without the C code.
+//!
+//! Rows are filtered with the usual minimum sum of absolute differences heuristic and
+//! compressed into a single zlib stream, with LZ77 matches encoded with the fixed
+//! Huffman codes of deflate.
+
+use std::io::{self, Write};
+
+/// Writes a PNG with 8 or 16 bits per channel. 16-bit samples are big endian in `data`.
+///
+/// The caller has checked that `data` holds width * height * components samples and that
+/// components is between 1 and 4.
+pub(crate) fn write_png<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    components: u32,
+    bit_depth: u8,
+    data: &[u8],
+) -> io::Result<()> {
+    let color_type = match components {
+        1 => 0, // grey
+        2 => 4, // grey and alpha
+        3 => 2, // RGB
+        _ => 6, // RGBA
+    };
+
+    writer.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'])?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // bit depth, colour type, deflate compression, adaptive filtering, no interlace
+    header.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+    write_chunk(writer, b"IHDR", &header)?;
+
+    let bytes_per_pixel = components as usize * bit_depth as usize / 8;
+    let filtered = filter_rows(data, width as usize * bytes_per_pixel, bytes_per_pixel);
+    // chunk lengths are limited to 2^31 - 1, split the stream to stay well below it
+    for data in zlib_compress(&filtered).chunks(1 << 20) {
+        write_chunk(writer, b"IDAT", data)?;
+    }
+
+    write_chunk(writer, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let crc = crc32_update(crc32_update(0xffff_ffff, kind), data) ^ 0xffff_ffff;
+    writer.write_all(&crc.to_be_bytes())
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    static TABLE: std::sync::OnceLock<[u32; 256]> = std::sync::OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = [0; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            let mut c = n as u32;
+            for _ in 0..8 {
+                c = if c & 1 != 0 {
+                    0xedb8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+            }
+            *entry = c;
+        }
+        table
+    });
+    for &byte in data {
+        crc = table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// Prefixes every row with the filter that gives the smallest sum of absolute values,
+/// which tends to compress best
+fn filter_rows(data: &[u8], stride: usize, bytes_per_pixel: usize) -> Vec<u8> {
+    let mut filtered = Vec::with_capacity(data.len() + data.len() / stride.max(1));
+    let zero_row = vec![0; stride];
+    let mut candidate = vec![0; stride];
+    let mut best = vec![0; stride];
+
+    for (y, row) in data.chunks_exact(stride).enumerate() {
+        let above = if y == 0 {
+            &zero_row[..]
+        } else {
+            &data[(y - 1) * stride..y * stride]
+        };
+
+        let mut best_filter = 0;
+        let mut best_cost = u64::MAX;
+        for filter in 0..5u8 {
+            for i in 0..stride {
+                let left = if i >= bytes_per_pixel {
+                    row[i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                let up = above[i];
+                let up_left = if i >= bytes_per_pixel {
+                    above[i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                let predicted = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    _ => paeth(left, up, up_left),
+                };
+                candidate[i] = row[i].wrapping_sub(predicted);
+            }
+            let cost = candidate
+                .iter()
+                .map(|&b| (b as i8).unsigned_abs() as u64)
+                .sum();
+            if cost < best_cost {
+                best_cost = cost;
+                best_filter = filter;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+
+        filtered.push(best_filter);
+        filtered.extend_from_slice(&best);
+    }
+    filtered
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// zlib header, one final deflate block with fixed Huffman codes, Adler-32 trailer
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32K window, default compression level, check bits so the header is a
+    // multiple of 31
+    let mut bits = BitWriter::new(vec![0x78, 0x9c]);
+
+    // BFINAL, then BTYPE 01 for fixed codes
+    bits.write(1, 1);
+    bits.write(1, 2);
+    lz77(data, |token| match token {
+        Token::Literal(byte) => write_literal_length(&mut bits, byte as u16),
+        Token::Match { length, distance } => {
+            let (symbol, extra_bits, extra) = length_code(length);
+            write_literal_length(&mut bits, symbol);
+            bits.write(extra, extra_bits);
+            let (symbol, extra_bits, extra) = distance_code(distance);
+            bits.write_huffman(symbol as u32, 5);
+            bits.write(extra, extra_bits);
+        }
+    });
+    write_literal_length(&mut bits, 256);
+
+    let mut output = bits.finish();
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 bytes is the most that can be summed before b overflows
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// Packs bits from the least significant end of each byte, as deflate wants
+struct BitWriter {
+    output: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new(output: Vec<u8>) -> BitWriter {
+        BitWriter {
+            output,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /// Writes the lowest `count` bits of value, least significant first
+    fn write(&mut self, value: u32, count: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.output.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are stored starting from their most significant bit
+    fn write_huffman(&mut self, code: u32, length: u32) {
+        self.write(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.output.push(self.buffer as u8);
+        }
+        self.output
+    }
+}
+
+fn write_literal_length(bits: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => bits.write_huffman(0x30 + symbol, 8),
+        144..=255 => bits.write_huffman(0x190 + symbol - 144, 9),
+        256..=279 => bits.write_huffman(symbol - 256, 7),
+        _ => bits.write_huffman(0xc0 + symbol - 280, 8),
+    }
+}
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Symbol, number of extra bits and their value for a match length
+fn length_code(length: u16) -> (u16, u32, u32) {
+    let index = LENGTH_BASES.partition_point(|&base| base <= length) - 1;
+    (
+        257 + index as u16,
+        LENGTH_EXTRA_BITS[index],
+        (length - LENGTH_BASES[index]) as u32,
+    )
+}
+
+/// Symbol, number of extra bits and their value for a match distance
+fn distance_code(distance: u16) -> (u16, u32, u32) {
+    let index = DISTANCE_BASES.partition_point(|&base| base <= distance) - 1;
+    (
+        index as u16,
+        DISTANCE_EXTRA_BITS[index],
+        (distance - DISTANCE_BASES[index]) as u32,
+    )
+}
+
+enum Token {
+    Literal(u8),
+    Match { length: u16, distance: u16 },
+}
+
+/// Greedy LZ77 over a 32K window, candidates are found through hash chains of the next
+/// 3 bytes
+fn lz77(data: &[u8], mut emit: impl FnMut(Token)) {
+    const WINDOW: usize = 32768;
+    const MIN_MATCH: usize = 3;
+    const MAX_MATCH: usize = 258;
+    const MAX_CHAIN: usize = 64;
+    const HASH_BITS: u32 = 15;
+    const NONE: usize = usize::MAX;
+
+    let hash = |i: usize| {
+        let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+        (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    };
+    let mut head = vec![NONE; 1 << HASH_BITS];
+    let mut previous = vec![NONE; data.len()];
+    let insert = |i: usize, head: &mut [usize], previous: &mut [usize]| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            previous[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best_length = 0;
+        let mut best_distance = 0;
+        if i + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(i)];
+            let mut steps = 0;
+            while candidate != NONE && i - candidate <= WINDOW && steps < MAX_CHAIN {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[i..i + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    best_length = length;
+                    best_distance = i - candidate;
+                    if length == max_length {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+                steps += 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            emit(Token::Match {
+                length: best_length as u16,
+                distance: best_distance as u16,
+            });
+            for j in i..i + best_length {
+                insert(j, &mut head, &mut previous);
+            }
+            i += best_length;
+        } else {
+            emit(Token::Literal(data[i]));
+            insert(i, &mut head, &mut previous);
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_known_values() {
+        let crc = |data: &[u8]| crc32_update(0xffff_ffff, data) ^ 0xffff_ffff;
+        assert_eq!(crc(b""), 0);
+        assert_eq!(crc(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc(b"IEND"), 0xae42_6082);
+        // updating in pieces gives the same result
+        let split = crc32_update(crc32_update(0xffff_ffff, b"1234"), b"56789") ^ 0xffff_ffff;
+        assert_eq!(split, 0xcbf4_3926);
+    }
+
+    #[test]
+    fn adler32_known_values() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"a"), 0x0062_0062);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        // long enough to need the modulo between chunks
+        let data = vec![0xff; 100_000];
+        let (mut a, mut b) = (1u64, 0u64);
+        for &byte in &data {
+            a = (a + byte as u64) % 65521;
+            b = (b + a) % 65521;
+        }
+        assert_eq!(adler32(&data), ((b << 16) | a) as u32);
+    }
+
+    #[test]
+    fn paeth_known_values() {
+        // (left, up, up left, expected)
+        let cases = [
+            (0, 0, 0, 0),
+            (20, 10, 10, 20),
+            (10, 20, 10, 20),
+            (5, 9, 7, 7),
+            (255, 0, 128, 128),
+            (10, 10, 20, 10),
+            (100, 50, 0, 100),
+            (0, 255, 255, 0),
+            (200, 100, 250, 100),
+        ];
+        for (a, b, c, expected) in cases {
+            assert_eq!(paeth(a, b, c), expected, "paeth({a}, {b}, {c})");
+        }
+    }
+
+    #[test]
+    fn chunks_are_framed_with_their_crc() {
+        let mut file = Vec::new();
+        write_png(&mut file, 1, 1, 3, 8, &[1, 2, 3]).unwrap();
+        assert_eq!(&file[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(
+            &file[8..33],
+            [
+                0, 0, 0, 13, b'I', b'H', b'D', b'R', 0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0, 0x90,
+                0x77, 0x53, 0xde
+            ]
+        );
+        assert_eq!(
+            &file[file.len() - 12..],
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+    }
+
+    /// Reads bits from the least significant end of each byte, as deflate stores them
+    struct BitReader<'a> {
+        data: &'a [u8],
+        position: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bit(&mut self) -> u32 {
+            let bit = (self.data[self.position / 8] >> (self.position % 8)) & 1;
+            self.position += 1;
+            bit as u32
+        }
+
+        fn bits(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |value, i| value | self.bit() << i)
+        }
+
+        /// Huffman codes start from their most significant bit
+        fn code(&mut self, length: u32) -> u32 {
+            (0..length).fold(0, |code, _| code << 1 | self.bit())
+        }
+
+        fn literal_length(&mut self) -> u32 {
+            let mut code = self.code(7);
+            if code <= 0x17 {
+                return 256 + code;
+            }
+            code = code << 1 | self.bit();
+            match code {
+                0x30..=0xbf => code - 0x30,
+                0xc0..=0xc7 => 280 + code - 0xc0,
+                _ => 144 + (code << 1 | self.bit()) - 0x190,
+            }
+        }
+    }
+
+    /// Decodes the zlib streams this encoder writes: one final block with fixed codes
+    fn inflate(stream: &[u8]) -> Vec<u8> {
+        assert_eq!(stream[..2], [0x78, 0x9c]);
+        assert_eq!(u16::from_be_bytes([stream[0], stream[1]]) % 31, 0);
+        let mut bits = BitReader {
+            data: &stream[2..stream.len() - 4],
+            position: 0,
+        };
+        assert_eq!(bits.bits(1), 1, "only one block");
+        assert_eq!(bits.bits(2), 1, "fixed Huffman codes");
+
+        let mut output: Vec<u8> = Vec::new();
+        loop {
+            let symbol = bits.literal_length();
+            match symbol {
+                0..=255 => output.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let index = symbol as usize - 257;
+                    let length =
+                        LENGTH_BASES[index] as usize + bits.bits(LENGTH_EXTRA_BITS[index]) as usize;
+                    let index = bits.code(5) as usize;
+                    let distance = DISTANCE_BASES[index] as usize
+                        + bits.bits(DISTANCE_EXTRA_BITS[index]) as usize;
+                    assert!(distance <= output.len() && distance <= 32768);
+                    for _ in 0..length {
+                        output.push(output[output.len() - distance]);
+                    }
+                }
+            }
+        }
+        let trailer = &stream[stream.len() - 4..];
+        assert_eq!(
+            u32::from_be_bytes(trailer.try_into().unwrap()),
+            adler32(&output)
+        );
+        output
+    }
+
+    /// Reverses the row filters
+    fn unfilter(filtered: &[u8], stride: usize, bytes_per_pixel: usize) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        for (y, row) in filtered.chunks_exact(stride + 1).enumerate() {
+            let start = data.len();
+            for (i, &value) in row[1..].iter().enumerate() {
+                let left = if i >= bytes_per_pixel {
+                    data[start + i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                let up = if y > 0 { data[start + i - stride] } else { 0 };
+                let up_left = if y > 0 && i >= bytes_per_pixel {
+                    data[start + i - stride - bytes_per_pixel]
+                } else {
+                    0
+                };
+                let predicted = match row[0] {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    4 => paeth(left, up, up_left),
+                    filter => panic!("unknown filter {filter}"),
+                };
+                data.push(value.wrapping_add(predicted));
+            }
+        }
+        data
+    }
+
+    /// Checks the framing of every chunk and returns the header and the decoded samples
+    fn decode(file: &[u8]) -> ([u8; 13], Vec<u8>) {
+        assert_eq!(&file[..8], b"\x89PNG\r\n\x1a\n");
+        let mut offset = 8;
+        let mut header = None;
+        let mut stream = Vec::new();
+        loop {
+            let length = u32::from_be_bytes(file[offset..offset + 4].try_into().unwrap());
+            let end = offset + 8 + length as usize;
+            let kind = &file[offset + 4..offset + 8];
+            let crc = u32::from_be_bytes(file[end..end + 4].try_into().unwrap());
+            assert_eq!(
+                crc32_update(0xffff_ffff, &file[offset + 4..end]) ^ 0xffff_ffff,
+                crc
+            );
+            let data = &file[offset + 8..end];
+            match kind {
+                b"IHDR" => header = Some(data.try_into().unwrap()),
+                b"IDAT" => stream.extend_from_slice(data),
+                b"IEND" => break,
+                _ => panic!("unexpected chunk"),
+            }
+            offset = end + 4;
+        }
+        assert_eq!(offset + 12, file.len());
+        let header: [u8; 13] = header.unwrap();
+        let width = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let bytes_per_pixel = match header[9] {
+            0 => 1,
+            4 => 2,
+            2 => 3,
+            _ => 4,
+        } * header[8] as usize
+            / 8;
+        let data = unfilter(&inflate(&stream), width * bytes_per_pixel, bytes_per_pixel);
+        (header, data)
+    }
+
+    /// Gradients, noise and repeated runs, so every filter and long matches get used
+    fn test_image(width: usize, height: usize, bytes_per_pixel: usize) -> Vec<u8> {
+        let mut state = 12345u32;
+        let mut data = Vec::with_capacity(width * height * bytes_per_pixel);
+        for y in 0..height {
+            for x in 0..width * bytes_per_pixel {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let value = match y % 4 {
+                    0 => (x + y) as u8,
+                    1 => (state >> 24) as u8,
+                    2 => 7,
+                    _ => (x * y) as u8 ^ (state >> 29) as u8,
+                };
+                data.push(value);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn round_trips() {
+        for (components, bit_depth, color_type) in [
+            (3, 8, 2),
+            (4, 8, 6),
+            (3, 16, 2),
+            (4, 16, 6),
+            (1, 8, 0),
+            (2, 16, 4),
+        ] {
+            for (width, height) in [(1, 1), (37, 23), (300, 40)] {
+                let bytes_per_pixel = components as usize * bit_depth as usize / 8;
+                let data = test_image(width, height, bytes_per_pixel);
+                let mut file = Vec::new();
+                write_png(
+                    &mut file,
+                    width as u32,
+                    height as u32,
+                    components,
+                    bit_depth,
+                    &data,
+                )
+                .unwrap();
+
+                let (header, decoded) = decode(&file);
+                assert_eq!(header[..4], (width as u32).to_be_bytes());
+                assert_eq!(header[4..8], (height as u32).to_be_bytes());
+                assert_eq!(header[8..], [bit_depth, color_type, 0, 0, 0]);
+                assert!(
+                    decoded == data,
+                    "{components} components at {bit_depth} bits, {width}x{height}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn repetitive_images_compress() {
+        let data = vec![200; 256 * 256 * 3];
+        let mut file = Vec::new();
+        write_png(&mut file, 256, 256, 3, 8, &data).unwrap();
+        assert!(file.len() < data.len() / 50);
+        assert_eq!(decode(&file).1, data);
+    }
+}
@@ -18,19 +18,19 @@ use std::num::Wrapping;
 /// Fast hash of an int32 into a different (unrecognizable) uint32.
 ///
 /// Returns an unsigned integer containing 32 reasonably-well-scrambled bits, based on the hash
-///	of a given (signed) integer input parameter (position/index) and [optional] seed.  Kind of
-///	like looking up a value in an infinitely large table of previously generated random numbers.
+/// of a given (signed) integer input parameter (position/index) and [optional] seed.  Kind of
+/// like looking up a value in an infinitely large table of previously generated random numbers.
 ///
 /// I call this particular approach SquirrelNoise5 (5th iteration of my 1D raw noise function).
 ///
 /// Many thanks to Peter Schmidt-Nielsen whose outstanding analysis helped identify a weakness
-///	in the SquirrelNoise3 code I originally used in my GDC 2017 talk, "Noise-based RNG".
-///	Version 5 avoids a noise repetition found in version 3 at extremely high position values
-///	caused by a lack of influence by some of the high input bits onto some of the low output bits.
+/// in the SquirrelNoise3 code I originally used in my GDC 2017 talk, "Noise-based RNG".
+/// Version 5 avoids a noise repetition found in version 3 at extremely high position values
+/// caused by a lack of influence by some of the high input bits onto some of the low output bits.
 ///
 /// The revised SquirrelNoise5 function ensures all input bits affect all output bits, and to
-///	(for me) a statistically acceptable degree.  I believe the worst-case here is in the amount
-///	of influence input position bit #30 has on output noise bit #0 (49.99%, vs. 50% ideal).
+/// (for me) a statistically acceptable degree.  I believe the worst-case here is in the amount
+/// of influence input position bit #30 has on output noise bit #0 (49.99%, vs. 50% ideal).
 ///
 pub fn squirrel_noise5(x: i32, seed: u32) -> i32 {
     const SQ5_BIT_NOISE1: u32 = 0xd2a80a3f; // 11010010101010000000101000111111
@@ -55,12 +55,244 @@ pub fn squirrel_noise5(x: i32, seed: u32) -> i32 {
 }
 
 //-----------------------------------------------------------------------------------------------
+// Raw pseudorandom noise functions (random-access / deterministic).  Basis of all other noise.
+//
+pub fn get1d_noise_uint(index_x: i32, seed: u32) -> u32 {
+    squirrel_noise5(index_x, seed) as u32
+}
+
+//-----------------------------------------------------------------------------------------------
+pub fn get2d_noise_uint(index_x: i32, index_y: i32, seed: u32) -> u32 {
+    const PRIME_NUMBER: i32 = 198491317; // Large prime number with non-boring bits
+    get1d_noise_uint(
+        index_x.wrapping_add(PRIME_NUMBER.wrapping_mul(index_y)),
+        seed,
+    )
+}
+
+//-----------------------------------------------------------------------------------------------
+pub fn get3d_noise_uint(index_x: i32, index_y: i32, index_z: i32, seed: u32) -> u32 {
+    const PRIME1: i32 = 198491317; // Large prime number with non-boring bits
+    const PRIME2: i32 = 6542989; // Large prime number with distinct and non-boring bits
+    get1d_noise_uint(
+        index_x
+            .wrapping_add(PRIME1.wrapping_mul(index_y))
+            .wrapping_add(PRIME2.wrapping_mul(index_z)),
+        seed,
+    )
+}
+
+//-----------------------------------------------------------------------------------------------
+pub fn get4d_noise_uint(index_x: i32, index_y: i32, index_z: i32, index_t: i32, seed: u32) -> u32 {
+    const PRIME1: i32 = 198491317; // Large prime number with non-boring bits
+    const PRIME2: i32 = 6542989; // Large prime number with distinct and non-boring bits
+    const PRIME3: i32 = 357239; // Large prime number with distinct and non-boring bits
+    get1d_noise_uint(
+        index_x
+            .wrapping_add(PRIME1.wrapping_mul(index_y))
+            .wrapping_add(PRIME2.wrapping_mul(index_z))
+            .wrapping_add(PRIME3.wrapping_mul(index_t)),
+        seed,
+    )
+}
+
+//-----------------------------------------------------------------------------------------------
+// Same functions, mapped to floats in [0,1] for convenience.
+//
+const ONE_OVER_MAX_UINT: f64 = 1.0 / 0xFFFFFFFFu32 as f64;
+
 pub fn get1d_noise_zero_to_one_f32(index: i32, seed: u32) -> f32 {
     get1d_noise_zero_to_one_f64(index, seed) as f32
 }
 
-//-----------------------------------------------------------------------------------------------
 pub fn get1d_noise_zero_to_one_f64(index: i32, seed: u32) -> f64 {
-    const ONE_OVER_MAX_UINT: f64 = 1.0 / 0xFFFFFFFFu32 as f64;
-    ONE_OVER_MAX_UINT * squirrel_noise5(index, seed) as f64
+    ONE_OVER_MAX_UINT * get1d_noise_uint(index, seed) as f64
+}
+
+//-----------------------------------------------------------------------------------------------
+pub fn get2d_noise_zero_to_one_f32(index_x: i32, index_y: i32, seed: u32) -> f32 {
+    get2d_noise_zero_to_one_f64(index_x, index_y, seed) as f32
+}
+
+pub fn get2d_noise_zero_to_one_f64(index_x: i32, index_y: i32, seed: u32) -> f64 {
+    ONE_OVER_MAX_UINT * get2d_noise_uint(index_x, index_y, seed) as f64
+}
+
+//-----------------------------------------------------------------------------------------------
+pub fn get3d_noise_zero_to_one_f32(index_x: i32, index_y: i32, index_z: i32, seed: u32) -> f32 {
+    get3d_noise_zero_to_one_f64(index_x, index_y, index_z, seed) as f32
+}
+
+pub fn get3d_noise_zero_to_one_f64(index_x: i32, index_y: i32, index_z: i32, seed: u32) -> f64 {
+    ONE_OVER_MAX_UINT * get3d_noise_uint(index_x, index_y, index_z, seed) as f64
+}
+
+//-----------------------------------------------------------------------------------------------
+pub fn get4d_noise_zero_to_one_f32(
+    index_x: i32,
+    index_y: i32,
+    index_z: i32,
+    index_t: i32,
+    seed: u32,
+) -> f32 {
+    get4d_noise_zero_to_one_f64(index_x, index_y, index_z, index_t, seed) as f32
+}
+
+pub fn get4d_noise_zero_to_one_f64(
+    index_x: i32,
+    index_y: i32,
+    index_z: i32,
+    index_t: i32,
+    seed: u32,
+) -> f64 {
+    ONE_OVER_MAX_UINT * get4d_noise_uint(index_x, index_y, index_z, index_t, seed) as f64
+}
+
+//-----------------------------------------------------------------------------------------------
+// Same functions, mapped to floats in [-1,1] for convenience.
+//
+const ONE_OVER_MAX_INT: f64 = 1.0 / 0x7FFFFFFF as f64;
+
+pub fn get1d_noise_neg_one_to_one_f32(index: i32, seed: u32) -> f32 {
+    get1d_noise_neg_one_to_one_f64(index, seed) as f32
+}
+
+pub fn get1d_noise_neg_one_to_one_f64(index: i32, seed: u32) -> f64 {
+    ONE_OVER_MAX_INT * get1d_noise_uint(index, seed) as i32 as f64
+}
+
+//-----------------------------------------------------------------------------------------------
+pub fn get2d_noise_neg_one_to_one_f32(index_x: i32, index_y: i32, seed: u32) -> f32 {
+    get2d_noise_neg_one_to_one_f64(index_x, index_y, seed) as f32
+}
+
+pub fn get2d_noise_neg_one_to_one_f64(index_x: i32, index_y: i32, seed: u32) -> f64 {
+    ONE_OVER_MAX_INT * get2d_noise_uint(index_x, index_y, seed) as i32 as f64
+}
+
+//-----------------------------------------------------------------------------------------------
+pub fn get3d_noise_neg_one_to_one_f32(index_x: i32, index_y: i32, index_z: i32, seed: u32) -> f32 {
+    get3d_noise_neg_one_to_one_f64(index_x, index_y, index_z, seed) as f32
+}
+
+pub fn get3d_noise_neg_one_to_one_f64(index_x: i32, index_y: i32, index_z: i32, seed: u32) -> f64 {
+    ONE_OVER_MAX_INT * get3d_noise_uint(index_x, index_y, index_z, seed) as i32 as f64
+}
+
+//-----------------------------------------------------------------------------------------------
+pub fn get4d_noise_neg_one_to_one_f32(
+    index_x: i32,
+    index_y: i32,
+    index_z: i32,
+    index_t: i32,
+    seed: u32,
+) -> f32 {
+    get4d_noise_neg_one_to_one_f64(index_x, index_y, index_z, index_t, seed) as f32
+}
+
+pub fn get4d_noise_neg_one_to_one_f64(
+    index_x: i32,
+    index_y: i32,
+    index_z: i32,
+    index_t: i32,
+    seed: u32,
+) -> f64 {
+    ONE_OVER_MAX_INT * get4d_noise_uint(index_x, index_y, index_z, index_t, seed) as i32 as f64
+}
+
+#[cfg(test)]
+mod tests {
+    //! Expected values come from the C++ SquirrelNoise5.hpp compiled with wrapping signed
+    //! arithmetic, the f64 ones are the doubles it computes before narrowing to float.
+
+    use super::*;
+
+    #[test]
+    fn squirrel_noise5_matches_reference() {
+        let cases: [(i32, u32, u32); 8] = [
+            (0, 0, 377036288),
+            (1, 0, 3365260061),
+            (-1, 0, 4210126164),
+            (42, 0, 468358277),
+            (0, 1, 603375697),
+            (123456789, 987654321, 2059660646),
+            (i32::MAX, u32::MAX, 379037034),
+            (i32::MIN, 7, 2576341466),
+        ];
+        for (x, seed, expected) in cases {
+            assert_eq!(
+                squirrel_noise5(x, seed) as u32,
+                expected,
+                "x {x} seed {seed}"
+            );
+        }
+        assert_eq!(squirrel_noise5(1, 0), -929707235);
+    }
+
+    #[test]
+    fn uint_noise_matches_reference() {
+        assert_eq!(get1d_noise_uint(42, 0), 468358277);
+        assert_eq!(get1d_noise_uint(-1, 0), 4210126164);
+        assert_eq!(get2d_noise_uint(3, -5, 17), 3881631355);
+        // the index overflows
+        assert_eq!(get2d_noise_uint(100000, 100000, 0), 1556189435);
+        assert_eq!(get3d_noise_uint(3, -5, 7, 17), 3974654260);
+        assert_eq!(get3d_noise_uint(-1000, 2000, -3000, 99), 737249319);
+        assert_eq!(get4d_noise_uint(3, -5, 7, -11, 17), 1221197916);
+        assert_eq!(get4d_noise_uint(1000000, -2, 3, 4000000, 5), 1901507418);
+    }
+
+    #[test]
+    fn zero_to_one_matches_reference() {
+        assert_eq!(get1d_noise_zero_to_one_f64(0, 0), 0.08778560163634494);
+        assert_eq!(get1d_noise_zero_to_one_f32(0, 0), 0.0877856);
+        assert_eq!(get1d_noise_zero_to_one_f64(42, 0), 0.10904815911991712);
+        assert_eq!(get1d_noise_zero_to_one_f32(42, 0), 0.10904816);
+        assert_eq!(get2d_noise_zero_to_one_f64(3, -5, 17), 0.9037627270221158);
+        assert_eq!(get2d_noise_zero_to_one_f32(3, -5, 17), 0.9037627);
+        assert_eq!(
+            get3d_noise_zero_to_one_f64(3, -5, 7, 17),
+            0.9254213098728613
+        );
+        assert_eq!(get3d_noise_zero_to_one_f32(3, -5, 7, 17), 0.9254213);
+        assert_eq!(
+            get4d_noise_zero_to_one_f64(3, -5, 7, -11, 17),
+            0.2843322968772455
+        );
+        assert_eq!(get4d_noise_zero_to_one_f32(3, -5, 7, -11, 17), 0.2843323);
+    }
+
+    #[test]
+    fn zero_to_one_converts_the_unsigned_noise() {
+        // the noise of index 1 has its top bit set, read as signed it would come out
+        // negative
+        assert!(squirrel_noise5(1, 0) < 0);
+        assert_eq!(get1d_noise_zero_to_one_f64(1, 0), 0.7835356662477216);
+        assert_eq!(get1d_noise_zero_to_one_f32(1, 0), 0.78353566);
+        assert_eq!(get1d_noise_zero_to_one_f64(2, 0), 0.7006853133674444);
+        assert_eq!(get1d_noise_zero_to_one_f32(2, 0), 0.7006853);
+    }
+
+    #[test]
+    fn neg_one_to_one_matches_reference() {
+        assert_eq!(get1d_noise_neg_one_to_one_f64(0, 0), 0.17557120331356824);
+        assert_eq!(get1d_noise_neg_one_to_one_f32(0, 0), 0.1755712);
+        assert_eq!(get1d_noise_neg_one_to_one_f64(1, 0), -0.432928668071017);
+        assert_eq!(get1d_noise_neg_one_to_one_f32(1, 0), -0.43292868);
+        assert_eq!(
+            get2d_noise_neg_one_to_one_f64(3, -5, 17),
+            -0.1924745464662437
+        );
+        assert_eq!(get2d_noise_neg_one_to_one_f32(3, -5, 17), -0.19247454);
+        assert_eq!(
+            get3d_noise_neg_one_to_one_f64(3, -5, 7, 17),
+            -0.14915738075466706
+        );
+        assert_eq!(get3d_noise_neg_one_to_one_f32(3, -5, 7, 17), -0.14915738);
+        assert_eq!(
+            get4d_noise_neg_one_to_one_f64(3, -5, 7, -11, 17),
+            0.5686645938868935
+        );
+        assert_eq!(get4d_noise_neg_one_to_one_f32(3, -5, 7, -11, 17), 0.5686646);
+    }
 }
@@ -1,46 +1,846 @@
-use std::ffi::{c_char, c_void, CString};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 
-/// Implements stbi_write_png
-///
-/// # Panics
-///
-/// When the size of data is not equal to width * height * components
+use crate::png;
+
+/// Whether BMP, TGA, JPEG and Radiance HDR images can be written, the pure-rust build
+/// only has the PNG encoder and fails them with [`WriteError::Unsupported`]
+pub const WRITES_ALL_FORMATS: bool = !cfg!(feature = "pure-rust");
+
+/// Whether images can be read, the pure-rust build has no decoder and fails [`load`],
+/// [`load_f32`] and [`is_hdr`] with [`LoadError::Unsupported`]
+pub const LOADS_IMAGES: bool = !cfg!(feature = "pure-rust");
+
+/// Why an image could not be written
+#[derive(Debug)]
+pub enum WriteError {
+    /// The path contains a nul byte
+    InvalidPath(String),
+    /// The buffer does not hold width * height * components values
+    SizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// Images have 1 (grey), 2 (grey, alpha), 3 (RGB) or 4 (RGBA) components
+    UnsupportedComponents(u32),
+    /// The image is too large for the encoder, which counts in 32-bit ints
+    DimensionOverflow {
+        width: u32,
+        height: u32,
+        components: u32,
+    },
+    /// Width or height is 0, image files need at least one pixel
+    EmptyImage,
+    /// The encoder gave up without saying why
+    Encode,
+    /// The format needs the C stb code, which the pure-rust feature leaves out
+    Unsupported(&'static str),
+    Io(io::Error),
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::InvalidPath(path) => write!(f, "{path}: invalid path"),
+            WriteError::SizeMismatch { expected, actual } => {
+                write!(f, "image buffer holds {actual} values, expected {expected}")
+            }
+            WriteError::UnsupportedComponents(components) => {
+                write!(f, "images have 1 to 4 components, got {components}")
+            }
+            WriteError::DimensionOverflow {
+                width,
+                height,
+                components,
+            } => write!(
+                f,
+                "{width}x{height} image with {components} components is too large to encode"
+            ),
+            WriteError::EmptyImage => write!(f, "cannot encode an empty image"),
+            WriteError::Encode => write!(f, "could not encode image"),
+            WriteError::Unsupported(format) => {
+                write!(
+                    f,
+                    "{format} images cannot be written in the pure-rust build"
+                )
+            }
+            WriteError::Io(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for WriteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WriteError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WriteError {
+    fn from(error: io::Error) -> Self {
+        WriteError::Io(error)
+    }
+}
+
+/// Checks that the buffer matches the image and that stb can address it
+fn check_dimensions(
+    width: u32,
+    height: u32,
+    components: u32,
+    len: usize,
+) -> Result<(), WriteError> {
+    if !(1..=4).contains(&components) {
+        return Err(WriteError::UnsupportedComponents(components));
+    }
+    if width == 0 || height == 0 {
+        return Err(WriteError::EmptyImage);
+    }
+
+    let overflow = WriteError::DimensionOverflow {
+        width,
+        height,
+        components,
+    };
+    // the stride of a row and the height are passed as ints
+    let stride = width as u64 * components as u64;
+    if stride > i32::MAX as u64 || height > i32::MAX as u32 {
+        return Err(overflow);
+    }
+    let expected = usize::try_from(stride * height as u64).map_err(|_| overflow)?;
+
+    if len != expected {
+        return Err(WriteError::SizeMismatch {
+            expected,
+            actual: len,
+        });
+    }
+    Ok(())
+}
+
+/// Writes a PNG file, see [`write_png_to`]
 pub fn write_png(
     filename: &str,
     width: u32,
     height: u32,
     components: u32,
     data: &[u8],
-) -> Result<(), ()> {
-    assert!((width * height * components) as usize == data.len());
+) -> Result<(), WriteError> {
+    write_file(filename, |writer| {
+        write_png_to(writer, width, height, components, data)
+    })
+}
 
-    let c_path = match CString::new(filename) {
-        Err(_) => return Err(()),
-        Ok(cstr) => cstr,
-    };
+/// Writes a PNG file with 16 bits per channel, see [`write_png_16_to`]
+pub fn write_png_16(
+    filename: &str,
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u16],
+) -> Result<(), WriteError> {
+    write_file(filename, |writer| {
+        write_png_16_to(writer, width, height, components, data)
+    })
+}
+
+/// Writes a BMP file, see [`write_bmp_to`]
+pub fn write_bmp(
+    filename: &str,
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u8],
+) -> Result<(), WriteError> {
+    write_file(filename, |writer| {
+        write_bmp_to(writer, width, height, components, data)
+    })
+}
+
+/// Writes a TGA file, see [`write_tga_to`]
+pub fn write_tga(
+    filename: &str,
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u8],
+) -> Result<(), WriteError> {
+    write_file(filename, |writer| {
+        write_tga_to(writer, width, height, components, data)
+    })
+}
+
+/// Writes a JPEG file, see [`write_jpg_to`]
+pub fn write_jpg(
+    filename: &str,
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u8],
+    quality: u8,
+) -> Result<(), WriteError> {
+    write_file(filename, |writer| {
+        write_jpg_to(writer, width, height, components, data, quality)
+    })
+}
+
+/// Writes a Radiance HDR file, see [`write_hdr_to`]
+pub fn write_hdr(
+    filename: &str,
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[f32],
+) -> Result<(), WriteError> {
+    write_file(filename, |writer| {
+        write_hdr_to(writer, width, height, components, data)
+    })
+}
+
+fn write_file(
+    filename: &str,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<(), WriteError>,
+) -> Result<(), WriteError> {
+    if filename.contains('\0') {
+        return Err(WriteError::InvalidPath(filename.to_string()));
+    }
+    let mut writer = BufWriter::new(File::create(filename)?);
+    write(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Implements stbi_write_png_to_func, streams the file to the writer as it is encoded.
+///
+/// With the pure-rust feature the Rust encoder behind [`write_png_16_to`] is used
+/// instead.
+pub fn write_png_to<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u8],
+) -> Result<(), WriteError> {
+    check_dimensions(width, height, components, data.len())?;
+    backend::write_png_to(writer, width, height, components, data)
+}
+
+/// Streams a PNG with 16 bits per channel to the writer. stb only writes 8 bits, so this
+/// always goes through the Rust encoder.
+pub fn write_png_16_to<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u16],
+) -> Result<(), WriteError> {
+    check_dimensions(width, height, components, data.len())?;
+    // PNG stores samples big endian
+    let bytes: Vec<u8> = data.iter().flat_map(|v| v.to_be_bytes()).collect();
+    png::write_png(writer, width, height, components, 16, &bytes)?;
+    Ok(())
+}
+
+/// Implements stbi_write_bmp_to_func, alpha is dropped
+pub fn write_bmp_to<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u8],
+) -> Result<(), WriteError> {
+    check_dimensions(width, height, components, data.len())?;
+    backend::write_bmp_to(writer, width, height, components, data)
+}
+
+/// Implements stbi_write_tga_to_func, run-length encoded
+pub fn write_tga_to<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u8],
+) -> Result<(), WriteError> {
+    check_dimensions(width, height, components, data.len())?;
+    backend::write_tga_to(writer, width, height, components, data)
+}
+
+/// Implements stbi_write_jpg_to_func, baseline JPEG. `quality` goes from 1 to 100, stb
+/// clamps anything outside.
+pub fn write_jpg_to<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u8],
+    quality: u8,
+) -> Result<(), WriteError> {
+    check_dimensions(width, height, components, data.len())?;
+    backend::write_jpg_to(writer, width, height, components, data, quality)
+}
+
+/// Implements stbi_write_hdr_to_func, run-length encoded Radiance RGBE
+pub fn write_hdr_to<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[f32],
+) -> Result<(), WriteError> {
+    check_dimensions(width, height, components, data.len())?;
+    backend::write_hdr_to(writer, width, height, components, data)
+}
+
+/// Encodes a PNG in memory, see [`write_png_to`]
+pub fn encode_png(
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u8],
+) -> Result<Vec<u8>, WriteError> {
+    let mut buffer = Vec::new();
+    write_png_to(&mut buffer, width, height, components, data)?;
+    Ok(buffer)
+}
+
+/// Encodes a PNG with 16 bits per channel in memory, see [`write_png_16_to`]
+pub fn encode_png_16(
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u16],
+) -> Result<Vec<u8>, WriteError> {
+    let mut buffer = Vec::new();
+    write_png_16_to(&mut buffer, width, height, components, data)?;
+    Ok(buffer)
+}
+
+/// Encodes a BMP in memory, see [`write_bmp_to`]
+pub fn encode_bmp(
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u8],
+) -> Result<Vec<u8>, WriteError> {
+    let mut buffer = Vec::new();
+    write_bmp_to(&mut buffer, width, height, components, data)?;
+    Ok(buffer)
+}
 
-    unsafe {
-        match stbi_write_png(
+/// Encodes a TGA in memory, see [`write_tga_to`]
+pub fn encode_tga(
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u8],
+) -> Result<Vec<u8>, WriteError> {
+    let mut buffer = Vec::new();
+    write_tga_to(&mut buffer, width, height, components, data)?;
+    Ok(buffer)
+}
+
+/// Encodes a JPEG in memory, see [`write_jpg_to`]
+pub fn encode_jpg(
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[u8],
+    quality: u8,
+) -> Result<Vec<u8>, WriteError> {
+    let mut buffer = Vec::new();
+    write_jpg_to(&mut buffer, width, height, components, data, quality)?;
+    Ok(buffer)
+}
+
+/// Encodes a Radiance HDR file in memory, see [`write_hdr_to`]
+pub fn encode_hdr(
+    width: u32,
+    height: u32,
+    components: u32,
+    data: &[f32],
+) -> Result<Vec<u8>, WriteError> {
+    let mut buffer = Vec::new();
+    write_hdr_to(&mut buffer, width, height, components, data)?;
+    Ok(buffer)
+}
+
+/// Why an image could not be read
+#[derive(Debug)]
+pub enum LoadError {
+    /// The path contains a nul byte, so it cannot be passed to C
+    InvalidPath(String),
+    /// stb_image could not open or decode the file, with the reason it gave
+    Decode { path: String, reason: String },
+    /// Loading needs the C stb code, which the pure-rust feature leaves out
+    Unsupported(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::InvalidPath(path) => write!(f, "{path}: invalid path"),
+            LoadError::Decode { path, reason } => write!(f, "{path}: {reason}"),
+            LoadError::Unsupported(path) => {
+                write!(f, "{path}: images cannot be loaded in the pure-rust build")
+            }
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+/// Pixels read from a file, stored row by row from the top with the channels of each
+/// pixel interleaved
+pub struct LoadedImage<P> {
+    pub width: u32,
+    pub height: u32,
+    pub components: u32,
+    pub data: Vec<P>,
+}
+
+/// Implements stbi_is_hdr, true for Radiance HDR files
+pub fn is_hdr(filename: &str) -> Result<bool, LoadError> {
+    backend::is_hdr(filename)
+}
+
+/// Implements stbi_load, reads any format stb_image supports as 8 bits per channel.
+///
+/// `components` is the number of channels to convert the image to, from 1 to 4.
+///
+/// # Panics
+///
+/// When components is not between 1 and 4
+pub fn load(filename: &str, components: u32) -> Result<LoadedImage<u8>, LoadError> {
+    assert!((1..=4).contains(&components));
+    backend::load(filename, components)
+}
+
+/// Implements stbi_loadf, reads HDR files without losing their range.
+///
+/// Other formats are converted with a plain 2.2 gamma curve rather than the sRGB one,
+/// so prefer [`load`] for them.
+///
+/// # Panics
+///
+/// When components is not between 1 and 4
+pub fn load_f32(filename: &str, components: u32) -> Result<LoadedImage<f32>, LoadError> {
+    assert!((1..=4).contains(&components));
+    backend::load_f32(filename, components)
+}
+
+/// Encoders and decoders from the stb headers compiled by build.rs
+#[cfg(not(feature = "pure-rust"))]
+mod backend {
+    use std::any::Any;
+    use std::ffi::{c_char, c_void, CStr, CString};
+    use std::io::{self, Write};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::Mutex;
+
+    use super::{LoadError, LoadedImage, WriteError};
+
+    pub(super) fn write_png_to<W: Write>(
+        writer: &mut W,
+        width: u32,
+        height: u32,
+        components: u32,
+        data: &[u8],
+    ) -> Result<(), WriteError> {
+        write_to_func(writer, |func, context| unsafe {
+            stbi_write_png_to_func(
+                func,
+                context,
+                width as i32,
+                height as i32,
+                components as i32,
+                data.as_ptr() as *const c_void,
+                (width * components) as i32,
+            )
+        })
+    }
+
+    pub(super) fn write_bmp_to<W: Write>(
+        writer: &mut W,
+        width: u32,
+        height: u32,
+        components: u32,
+        data: &[u8],
+    ) -> Result<(), WriteError> {
+        write_to_func(writer, |func, context| unsafe {
+            stbi_write_bmp_to_func(
+                func,
+                context,
+                width as i32,
+                height as i32,
+                components as i32,
+                data.as_ptr() as *const c_void,
+            )
+        })
+    }
+
+    pub(super) fn write_tga_to<W: Write>(
+        writer: &mut W,
+        width: u32,
+        height: u32,
+        components: u32,
+        data: &[u8],
+    ) -> Result<(), WriteError> {
+        write_to_func(writer, |func, context| unsafe {
+            stbi_write_tga_to_func(
+                func,
+                context,
+                width as i32,
+                height as i32,
+                components as i32,
+                data.as_ptr() as *const c_void,
+            )
+        })
+    }
+
+    pub(super) fn write_jpg_to<W: Write>(
+        writer: &mut W,
+        width: u32,
+        height: u32,
+        components: u32,
+        data: &[u8],
+        quality: u8,
+    ) -> Result<(), WriteError> {
+        write_to_func(writer, |func, context| unsafe {
+            stbi_write_jpg_to_func(
+                func,
+                context,
+                width as i32,
+                height as i32,
+                components as i32,
+                data.as_ptr() as *const c_void,
+                quality as i32,
+            )
+        })
+    }
+
+    pub(super) fn write_hdr_to<W: Write>(
+        writer: &mut W,
+        width: u32,
+        height: u32,
+        components: u32,
+        data: &[f32],
+    ) -> Result<(), WriteError> {
+        write_to_func(writer, |func, context| unsafe {
+            stbi_write_hdr_to_func(
+                func,
+                context,
+                width as i32,
+                height as i32,
+                components as i32,
+                data.as_ptr(),
+            )
+        })
+    }
+
+    /// Where the write callback sends the bytes stb produces
+    struct WriteContext<'a, W> {
+        writer: &'a mut W,
+        /// stb cannot be stopped once it started, so only the first error is kept and the
+        /// rest of the output is dropped
+        error: Option<io::Error>,
+        /// A panic of the writer, unwinding through the C frames of stb would abort, so
+        /// it is caught and resumed once stb returns
+        panic: Option<Box<dyn Any + Send>>,
+    }
+
+    extern "C" fn write_callback<W: Write>(context: *mut c_void, data: *mut c_void, size: i32) {
+        let context = unsafe { &mut *(context as *mut WriteContext<W>) };
+        if context.error.is_some() || context.panic.is_some() || size <= 0 {
+            return;
+        }
+        let data = unsafe { std::slice::from_raw_parts(data as *const u8, size as usize) };
+        match panic::catch_unwind(AssertUnwindSafe(|| context.writer.write_all(data))) {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => context.error = Some(error),
+            Err(payload) => context.panic = Some(payload),
+        }
+    }
+
+    fn write_to_func<W: Write>(
+        writer: &mut W,
+        write: impl FnOnce(StbiWriteFunc, *mut c_void) -> i32,
+    ) -> Result<(), WriteError> {
+        let mut context = WriteContext {
+            writer,
+            error: None,
+            panic: None,
+        };
+        let result = write(
+            write_callback::<W>,
+            &mut context as *mut WriteContext<W> as *mut c_void,
+        );
+        if let Some(payload) = context.panic {
+            panic::resume_unwind(payload);
+        }
+        match (context.error, result) {
+            (Some(error), _) => Err(WriteError::Io(error)),
+            (None, 0) => Err(WriteError::Encode),
+            (None, _) => Ok(()),
+        }
+    }
+
+    // stb_image reports failures through a global, hold this while loading and reading it
+    static LOAD_LOCK: Mutex<()> = Mutex::new(());
+
+    pub(super) fn is_hdr(filename: &str) -> Result<bool, LoadError> {
+        let c_path =
+            CString::new(filename).map_err(|_| LoadError::InvalidPath(filename.to_string()))?;
+        unsafe { Ok(stbi_is_hdr(c_path.as_ptr()) != 0) }
+    }
+
+    pub(super) fn load(filename: &str, components: u32) -> Result<LoadedImage<u8>, LoadError> {
+        load_with(filename, components, |path, x, y, comp, req_comp| unsafe {
+            stbi_load(path, x, y, comp, req_comp)
+        })
+    }
+
+    pub(super) fn load_f32(filename: &str, components: u32) -> Result<LoadedImage<f32>, LoadError> {
+        load_with(filename, components, |path, x, y, comp, req_comp| unsafe {
+            stbi_loadf(path, x, y, comp, req_comp)
+        })
+    }
+
+    fn load_with<P: Copy>(
+        filename: &str,
+        components: u32,
+        load: impl FnOnce(*const c_char, &mut i32, &mut i32, &mut i32, i32) -> *mut P,
+    ) -> Result<LoadedImage<P>, LoadError> {
+        let c_path =
+            CString::new(filename).map_err(|_| LoadError::InvalidPath(filename.to_string()))?;
+
+        let _guard = LOAD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let (mut width, mut height, mut file_components) = (0, 0, 0);
+        let pixels = load(
             c_path.as_ptr(),
-            width as i32,
-            height as i32,
+            &mut width,
+            &mut height,
+            &mut file_components,
             components as i32,
-            data.as_ptr() as *const c_void,
-            (width * components) as i32,
-        ) {
-            0 => Err(()),
-            _ => Ok(()),
+        );
+
+        if pixels.is_null() {
+            let reason = unsafe {
+                let reason = stbi_failure_reason();
+                if reason.is_null() {
+                    "unknown error".to_string()
+                } else {
+                    CStr::from_ptr(reason).to_string_lossy().into_owned()
+                }
+            };
+            return Err(LoadError::Decode {
+                path: filename.to_string(),
+                reason,
+            });
         }
+
+        let len = width as usize * height as usize * components as usize;
+        let data = unsafe {
+            let data = std::slice::from_raw_parts(pixels, len).to_vec();
+            stbi_image_free(pixels as *mut c_void);
+            data
+        };
+
+        Ok(LoadedImage {
+            width: width as u32,
+            height: height as u32,
+            components,
+            data,
+        })
+    }
+
+    extern "C" {
+        fn stbi_is_hdr(filename: *const c_char) -> i32;
+
+        fn stbi_load(
+            filename: *const c_char,
+            x: *mut i32,
+            y: *mut i32,
+            channels_in_file: *mut i32,
+            desired_channels: i32,
+        ) -> *mut u8;
+
+        fn stbi_loadf(
+            filename: *const c_char,
+            x: *mut i32,
+            y: *mut i32,
+            channels_in_file: *mut i32,
+            desired_channels: i32,
+        ) -> *mut f32;
+
+        fn stbi_failure_reason() -> *const c_char;
+
+        fn stbi_image_free(retval_from_stbi_load: *mut c_void);
+
+        fn stbi_write_png_to_func(
+            func: StbiWriteFunc,
+            context: *mut c_void,
+            w: i32,
+            h: i32,
+            comp: i32,
+            data: *const c_void,
+            stride_in_bytes: i32,
+        ) -> i32;
+
+        fn stbi_write_bmp_to_func(
+            func: StbiWriteFunc,
+            context: *mut c_void,
+            w: i32,
+            h: i32,
+            comp: i32,
+            data: *const c_void,
+        ) -> i32;
+
+        fn stbi_write_tga_to_func(
+            func: StbiWriteFunc,
+            context: *mut c_void,
+            w: i32,
+            h: i32,
+            comp: i32,
+            data: *const c_void,
+        ) -> i32;
+
+        fn stbi_write_jpg_to_func(
+            func: StbiWriteFunc,
+            context: *mut c_void,
+            x: i32,
+            y: i32,
+            comp: i32,
+            data: *const c_void,
+            quality: i32,
+        ) -> i32;
+
+        fn stbi_write_hdr_to_func(
+            func: StbiWriteFunc,
+            context: *mut c_void,
+            w: i32,
+            h: i32,
+            comp: i32,
+            data: *const f32,
+        ) -> i32;
+    }
+
+    type StbiWriteFunc = extern "C" fn(context: *mut c_void, data: *mut c_void, size: i32);
+}
+
+/// Without a C compiler only PNG can be written, through the Rust encoder
+#[cfg(feature = "pure-rust")]
+mod backend {
+    use std::io::Write;
+
+    use super::{png, LoadError, LoadedImage, WriteError};
+
+    pub(super) fn write_png_to<W: Write>(
+        writer: &mut W,
+        width: u32,
+        height: u32,
+        components: u32,
+        data: &[u8],
+    ) -> Result<(), WriteError> {
+        png::write_png(writer, width, height, components, 8, data)?;
+        Ok(())
+    }
+
+    pub(super) fn write_bmp_to<W: Write>(
+        _: &mut W,
+        _: u32,
+        _: u32,
+        _: u32,
+        _: &[u8],
+    ) -> Result<(), WriteError> {
+        Err(WriteError::Unsupported("BMP"))
+    }
+
+    pub(super) fn write_tga_to<W: Write>(
+        _: &mut W,
+        _: u32,
+        _: u32,
+        _: u32,
+        _: &[u8],
+    ) -> Result<(), WriteError> {
+        Err(WriteError::Unsupported("TGA"))
+    }
+
+    pub(super) fn write_jpg_to<W: Write>(
+        _: &mut W,
+        _: u32,
+        _: u32,
+        _: u32,
+        _: &[u8],
+        _: u8,
+    ) -> Result<(), WriteError> {
+        Err(WriteError::Unsupported("JPEG"))
+    }
+
+    pub(super) fn write_hdr_to<W: Write>(
+        _: &mut W,
+        _: u32,
+        _: u32,
+        _: u32,
+        _: &[f32],
+    ) -> Result<(), WriteError> {
+        Err(WriteError::Unsupported("Radiance HDR"))
+    }
+
+    pub(super) fn is_hdr(filename: &str) -> Result<bool, LoadError> {
+        Err(LoadError::Unsupported(filename.to_string()))
+    }
+
+    pub(super) fn load(filename: &str, _: u32) -> Result<LoadedImage<u8>, LoadError> {
+        Err(LoadError::Unsupported(filename.to_string()))
+    }
+
+    pub(super) fn load_f32(filename: &str, _: u32) -> Result<LoadedImage<f32>, LoadError> {
+        Err(LoadError::Unsupported(filename.to_string()))
     }
 }
 
-extern "C" {
-    fn stbi_write_png(
-        filename: *const c_char,
-        w: i32,
-        h: i32,
-        comp: i32,
-        data: *const c_void,
-        stride_in_bytes: i32,
-    ) -> i32;
+#[cfg(all(test, not(feature = "pure-rust")))]
+mod tests {
+    use std::io::{self, Write};
+
+    use super::*;
+
+    struct PanickingWriter;
+
+    impl Write for PanickingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            panic!("writer panicked");
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "writer panicked")]
+    fn writer_panic_is_resumed_after_stb_returns() {
+        let _ = write_bmp_to(&mut PanickingWriter, 2, 2, 3, &[0; 12]);
+    }
+
+    #[test]
+    fn writer_error_is_returned() {
+        let result = write_tga_to(&mut FailingWriter, 2, 2, 3, &[0; 12]);
+        assert!(matches!(result, Err(WriteError::Io(error)) if error.to_string() == "disk full"));
+    }
 }
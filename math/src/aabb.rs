@@ -0,0 +1,275 @@
+use std::ops::Index;
+
+use crate::{floatops::Float, Interval, Point3, Ray};
+
+/// Axis-aligned bounding box, stored as one interval per axis
+#[derive(Clone, Copy)]
+pub struct Aabb<T> {
+    pub x: Interval<T>,
+    pub y: Interval<T>,
+    pub z: Interval<T>,
+}
+
+impl<T> Aabb<T> {
+    pub fn new(x: Interval<T>, y: Interval<T>, z: Interval<T>) -> Aabb<T> {
+        Aabb { x, y, z }
+    }
+}
+
+impl<T> Aabb<T>
+where
+    T: Float,
+{
+    // creation
+
+    pub fn empty() -> Aabb<T> {
+        Aabb::new(Interval::empty(), Interval::empty(), Interval::empty())
+    }
+
+    /// box with the two points as opposite corners, in any order
+    pub fn from_points(a: Point3<T>, b: Point3<T>) -> Aabb<T> {
+        Aabb::new(
+            Interval::new(a.x.min(b.x), a.x.max(b.x)),
+            Interval::new(a.y.min(b.y), a.y.max(b.y)),
+            Interval::new(a.z.min(b.z), a.z.max(b.z)),
+        )
+    }
+
+    /// smallest box that contains both boxes
+    pub fn surrounding(a: &Aabb<T>, b: &Aabb<T>) -> Aabb<T> {
+        a.union(b)
+    }
+
+    /// smallest box that contains all the points, empty if there are none
+    pub fn surrounding_points<I: IntoIterator<Item = Point3<T>>>(points: I) -> Aabb<T> {
+        points
+            .into_iter()
+            .fold(Aabb::empty(), |bbox, p| bbox.union_point(p))
+    }
+
+    // operations
+
+    pub fn union(&self, other: &Aabb<T>) -> Aabb<T> {
+        Aabb::new(
+            Interval::surrounding(&self.x, &other.x),
+            Interval::surrounding(&self.y, &other.y),
+            Interval::surrounding(&self.z, &other.z),
+        )
+    }
+
+    pub fn union_point(&self, p: Point3<T>) -> Aabb<T> {
+        Aabb::new(
+            Interval::new(self.x.min.min(p.x), self.x.max.max(p.x)),
+            Interval::new(self.y.min.min(p.y), self.y.max.max(p.y)),
+            Interval::new(self.z.min.min(p.z), self.z.max.max(p.z)),
+        )
+    }
+
+    /// widens any axis thinner than delta, so flat geometry (quads, triangles aligned with
+    /// an axis) still has a volume that rays can hit
+    pub fn pad_to_minimums(&self, delta: T) -> Aabb<T> {
+        let pad = |interval: &Interval<T>| {
+            if interval.size() < delta {
+                interval.expand(delta)
+            } else {
+                *interval
+            }
+        };
+        Aabb::new(pad(&self.x), pad(&self.y), pad(&self.z))
+    }
+
+    // queries
+
+    pub fn is_empty(&self) -> bool {
+        self.x.min > self.x.max || self.y.min > self.y.max || self.z.min > self.z.max
+    }
+
+    /// index of the axis with the largest extent, 0 for x, 1 for y and 2 for z
+    pub fn longest_axis(&self) -> u8 {
+        let (x, y, z) = (self.x.size(), self.y.size(), self.z.size());
+        if x > y && x > z {
+            0
+        } else if y > z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn centroid(&self) -> Point3<T> {
+        let half = T::constant(0.5);
+        Point3::new(
+            (self.x.min + self.x.max) * half,
+            (self.y.min + self.y.max) * half,
+            (self.z.min + self.z.max) * half,
+        )
+    }
+
+    /// total area of the six faces, zero for empty boxes
+    pub fn surface_area(&self) -> T {
+        let zero = T::constant(0.0);
+        let dx = self.x.size().max(zero);
+        let dy = self.y.size().max(zero);
+        let dz = self.z.size().max(zero);
+        T::constant(2.0) * (dx * dy + dy * dz + dz * dx)
+    }
+
+    /// slab test, checks if the ray crosses the box inside ray_t
+    ///
+    /// The far distance of each slab is scaled up by the worst case rounding error of its
+    /// computation, so rays that graze an edge or travel inside a flat box are not lost.
+    /// Directions with a zero component give infinite slab distances, and the NaN from
+    /// an origin right on that slab is ignored by the min/max comparisons. The slab ends
+    /// are ordered by the sign of the direction rather than by value, so the inverted
+    /// intervals of an empty box never overlap the ray.
+    pub fn hit(&self, ray: &Ray<T>, mut ray_t: Interval<T>) -> bool {
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let error_scale = T::one() + T::constant(2.0) * gamma::<T>(3);
+        for axis in 0..3 {
+            let inv_d = T::one() / direction[axis];
+            let mut t0 = (self[axis].min - origin[axis]) * inv_d;
+            let mut t1 = (self[axis].max - origin[axis]) * inv_d;
+            if inv_d < T::constant(0.0) {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t1 *= error_scale;
+            ray_t.min = ray_t.min.max(t0);
+            ray_t.max = ray_t.max.min(t1);
+            if ray_t.max < ray_t.min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Bound on the relative rounding error of n chained floating point operations
+fn gamma<T: Float>(n: i32) -> T {
+    let n = T::from_i32(n).unwrap();
+    let half_epsilon = T::epsilon() * T::constant(0.5);
+    n * half_epsilon / (T::one() - n * half_epsilon)
+}
+
+// overrides aabb[axis]
+impl<T, Idx: Into<i64>> Index<Idx> for Aabb<T> {
+    type Output = Interval<T>;
+
+    fn index(&self, index: Idx) -> &Self::Output {
+        match index.into() {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("index out of bounds"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector3;
+
+    fn unit_box() -> Aabb<f64> {
+        Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    fn ray(origin: (f64, f64, f64), direction: (f64, f64, f64)) -> Ray<f64> {
+        Ray::new(
+            Point3::new(origin.0, origin.1, origin.2),
+            Vector3::new(direction.0, direction.1, direction.2),
+        )
+    }
+
+    fn forward() -> Interval<f64> {
+        Interval::new(0.001, f64::INFINITY)
+    }
+
+    #[test]
+    fn axis_parallel_rays() {
+        let bbox = unit_box();
+        for axis in 0..3 {
+            let mut origin = [0.5; 3];
+            let mut direction = [0.0; 3];
+            origin[axis] = -1.0;
+            direction[axis] = 1.0;
+            let towards = ray(
+                (origin[0], origin[1], origin[2]),
+                (direction[0], direction[1], direction[2]),
+            );
+            let away = ray(
+                (origin[0], origin[1], origin[2]),
+                (-direction[0], -direction[1], -direction[2]),
+            );
+            assert!(bbox.hit(&towards, forward()), "axis {axis}");
+            assert!(!bbox.hit(&away, forward()), "axis {axis}");
+        }
+        // parallel to x but outside the box in y, with negative zero components
+        let beside = ray((-1.0, 1.5, 0.5), (1.0, -0.0, -0.0));
+        assert!(!bbox.hit(&beside, forward()));
+    }
+
+    #[test]
+    fn ray_starting_inside() {
+        let bbox = unit_box();
+        let r = ray((0.5, 0.5, 0.5), (1.0, 2.0, 3.0));
+        assert!(bbox.hit(&r, forward()));
+        assert!(bbox.hit(&r, Interval::new(0.001, 0.01)));
+        let r = ray((0.5, 0.5, 0.5), (-1.0, -2.0, -3.0));
+        assert!(bbox.hit(&r, forward()));
+    }
+
+    #[test]
+    fn ray_interval_is_respected() {
+        let bbox = unit_box();
+        let r = ray((-2.0, 0.5, 0.5), (1.0, 0.0, 0.0));
+        assert!(bbox.hit(&r, Interval::new(0.0, 2.0)));
+        assert!(!bbox.hit(&r, Interval::new(0.0, 1.5)));
+        assert!(!bbox.hit(&r, Interval::new(3.5, 10.0)));
+    }
+
+    #[test]
+    fn grazing_rays() {
+        let bbox = unit_box();
+        // along a face
+        assert!(bbox.hit(&ray((-1.0, 1.0, 0.5), (1.0, 0.0, 0.0)), forward()));
+        // along an edge
+        assert!(bbox.hit(&ray((-1.0, 1.0, 1.0), (1.0, 0.0, 0.0)), forward()));
+        // through a corner only
+        assert!(bbox.hit(&ray((-1.0, 1.0, 0.5), (1.0, -1.0, 0.0)), forward()));
+        // just past the corner
+        assert!(!bbox.hit(&ray((-1.0, 0.999, 0.5), (1.0, -1.0, 0.0)), forward()));
+        // just above the face
+        assert!(!bbox.hit(&ray((-1.0, 1.001, 0.5), (1.0, 0.0, 0.0)), forward()));
+    }
+
+    #[test]
+    fn flat_box_after_padding() {
+        let flat = Aabb::from_points(Point3::new(0.0, 0.0, 2.0), Point3::new(1.0, 1.0, 2.0))
+            .pad_to_minimums(0.0001);
+        assert!(flat.z.size() > 0.00009);
+        assert_eq!(flat.x.size(), 1.0);
+        // through the plane
+        assert!(flat.hit(&ray((0.5, 0.5, 0.0), (0.0, 0.0, 1.0)), forward()));
+        assert!(flat.hit(&ray((0.5, 0.5, 0.0), (0.1, 0.2, 1.0)), forward()));
+        // inside the plane
+        assert!(flat.hit(&ray((-1.0, 0.5, 2.0), (1.0, 0.0, 0.0)), forward()));
+        // beside it
+        assert!(!flat.hit(&ray((1.5, 0.5, 0.0), (0.0, 0.0, 1.0)), forward()));
+    }
+
+    #[test]
+    fn empty_box_is_never_hit() {
+        let empty = Aabb::<f64>::empty();
+        assert!(empty.is_empty());
+        for direction in [
+            (1.0, 0.0, 0.0),
+            (-1.0, 0.0, 0.0),
+            (0.0, 0.0, 0.0),
+            (1.0, -2.0, 3.0),
+        ] {
+            assert!(!empty.hit(&ray((0.0, 0.0, 0.0), direction), forward()));
+            assert!(!empty.hit(&ray((0.0, 0.0, 0.0), direction), Interval::universe()));
+        }
+    }
+}
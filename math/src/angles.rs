@@ -1,6 +1,8 @@
 use super::floatops::Float;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Degrees<T>(pub T);
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Radians<T>(pub T);
 
 impl<T> From<Degrees<T>> for Radians<T>
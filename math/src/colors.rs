@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Div, Index, IndexMut, Mul};
 
-use crate::{floatops::Float, RandomSource};
+use crate::{floatops::Float, Dither, RandomSource};
 
 #[derive(Clone, Copy)]
 pub struct RGB<T> {
@@ -40,23 +40,81 @@ where
     pub fn black() -> Self {
         Self::new(T::constant(0.0), T::constant(0.0), T::constant(0.0))
     }
-}
 
-impl<T> RGB<T>
-where
-    T: Float + num::traits::cast::AsPrimitive<u8>,
-{
+    /// Decodes 8-bit sRGB triplets to linear colours with the exact sRGB transfer function
+    ///
+    /// # Panics
+    ///
+    /// When the size of input is not a multiple of 3
+    pub fn from_srgb_array(input: &[u8]) -> Vec<RGB<T>> {
+        assert!(input.len().is_multiple_of(3));
+        let table: Vec<T> = (0..=255u8).map(srgb_to_linear).collect();
+        input
+            .chunks_exact(3)
+            .map(|c| {
+                RGB::new(
+                    table[c[0] as usize],
+                    table[c[1] as usize],
+                    table[c[2] as usize],
+                )
+            })
+            .collect()
+    }
+
+    /// Encodes linear colours to 8-bit sRGB triplets with the exact sRGB transfer function.
+    ///
+    /// Values are clamped to [0, 1] (NaN becomes 0) and rounded to the nearest level.
     pub fn to_srgb_array(input: &[RGB<T>]) -> Vec<u8> {
-        let mut result = Vec::with_capacity(input.len());
-        for i in input {
-            result.push((i.r.powf(T::constant(1.0 / 2.2)) * T::constant(255.0)).as_());
-            result.push((i.g.powf(T::constant(1.0 / 2.2)) * T::constant(255.0)).as_());
-            result.push((i.b.powf(T::constant(1.0 / 2.2)) * T::constant(255.0)).as_());
+        Self::to_srgb_array_dithered(input, 1, Dither::None)
+    }
+
+    /// Same as [`RGB::to_srgb_array`], with dithering added in sRGB space before
+    /// rounding. `width` is the number of pixels in a row of the image, the dither
+    /// pattern is tiled over it.
+    ///
+    /// # Panics
+    ///
+    /// When width is 0 or input is not a whole number of rows
+    pub fn to_srgb_array_dithered(input: &[RGB<T>], width: u32, dither: Dither) -> Vec<u8> {
+        assert!(width > 0 && input.len().is_multiple_of(width as usize));
+        let mut result = Vec::with_capacity(input.len() * 3);
+        for (index, pixel) in input.iter().enumerate() {
+            let x = (index % width as usize) as u32;
+            let y = (index / width as usize) as u32;
+            let offset = dither.offset(x, y);
+            for c in [pixel.r, pixel.g, pixel.b] {
+                let encoded = linear_to_srgb(c).to_f64().unwrap() * 255.0 + offset;
+                result.push(encoded.round().clamp(0.0, 255.0) as u8);
+            }
         }
         result
     }
 }
 
+fn linear_to_srgb<T: Float>(c: T) -> T {
+    let constant = |c: f64| T::from_f64(c).unwrap();
+    if c.is_nan() || c <= T::zero() {
+        T::zero()
+    } else if c >= T::one() {
+        T::one()
+    } else if c <= constant(0.0031308) {
+        c * constant(12.92)
+    } else {
+        constant(1.055) * c.powf(constant(1.0 / 2.4)) - constant(0.055)
+    }
+}
+
+fn srgb_to_linear<T: Float>(value: u8) -> T {
+    // f32 constants are not precise enough to decode 255 to exactly 1 in f64
+    let constant = |c: f64| T::from_f64(c).unwrap();
+    let c = T::from_u8(value).unwrap() / constant(255.0);
+    if c <= constant(0.04045) {
+        c / constant(12.92)
+    } else {
+        ((c + constant(0.055)) / constant(1.055)).powf(constant(2.4))
+    }
+}
+
 // overrides rgb[idx]
 impl<T, Idx: Into<i64>> Index<Idx> for RGB<T> {
     type Output = T;
@@ -123,3 +181,103 @@ impl<T: Float> AddAssign for RGB<T> {
         self.b += rhs.b;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(c: f64) -> u8 {
+        RGB::to_srgb_array(&[RGB::new(c, c, c)])[0]
+    }
+
+    /// Linear value whose sRGB encoding scaled to [0, 255] is exactly `level`
+    fn decode_level(level: f64) -> f64 {
+        let s = level / 255.0;
+        if s <= 0.04045 {
+            s / 12.92
+        } else {
+            ((s + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    #[test]
+    fn transfer_function_end_points() {
+        assert_eq!(encode(0.0), 0);
+        assert_eq!(encode(-0.0), 0);
+        // end of the linear segment, 0.0031308 * 12.92 * 255 = 10.31
+        assert_eq!(encode(0.0031308), 10);
+        assert_eq!(encode(0.0031309), 10);
+        assert_eq!(encode(1.0), 255);
+        assert_eq!(encode(0.5), 188);
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        assert_eq!(encode(1.0001), 255);
+        assert_eq!(encode(100.0), 255);
+        assert_eq!(encode(f64::INFINITY), 255);
+        assert_eq!(encode(-0.0001), 0);
+        assert_eq!(encode(-100.0), 0);
+        assert_eq!(encode(f64::NEG_INFINITY), 0);
+        assert_eq!(encode(f64::NAN), 0);
+        assert_eq!(
+            RGB::to_srgb_array(&[RGB::new(f32::NAN, 2.0, -1.0)]),
+            [0, 255, 0]
+        );
+    }
+
+    #[test]
+    fn rounds_to_the_nearest_level() {
+        // half steps in the linear segment and in the curve
+        for level in [0.5, 5.5, 10.5, 127.5, 200.5, 254.5] {
+            let c = decode_level(level);
+            let below = level.floor() as u8;
+            assert_eq!(encode(c * (1.0 - 1e-9)), below, "just below {level}");
+            assert_eq!(encode(c * (1.0 + 1e-9)), below + 1, "just above {level}");
+        }
+    }
+
+    #[test]
+    fn decoding_then_encoding_is_lossless() {
+        let levels: Vec<u8> = (0..=255).flat_map(|v| [v, v, v]).collect();
+        let decoded = RGB::<f64>::from_srgb_array(&levels);
+        assert_eq!(RGB::to_srgb_array(&decoded), levels);
+        let decoded = RGB::<f32>::from_srgb_array(&levels);
+        assert_eq!(RGB::to_srgb_array(&decoded), levels);
+    }
+
+    #[test]
+    fn dithering_without_a_pattern_changes_nothing() {
+        let pixels: Vec<RGB<f64>> = (0..64)
+            .map(|i| RGB::new(i as f64 / 63.0, 0.25, 0.75))
+            .collect();
+        assert_eq!(
+            RGB::to_srgb_array_dithered(&pixels, 8, Dither::None),
+            RGB::to_srgb_array(&pixels)
+        );
+    }
+
+    #[test]
+    fn dithering_stays_within_one_level_and_keeps_the_average() {
+        for dither in [Dither::Ordered, Dither::BlueNoise] {
+            // a flat field between two levels
+            let c = decode_level(100.3);
+            let pixels = vec![RGB::new(c, c, c); 64 * 64];
+            let dithered = RGB::to_srgb_array_dithered(&pixels, 64, dither);
+            assert!(dithered.iter().all(|&v| v == 100 || v == 101));
+            let mean = dithered.iter().map(|&v| v as f64).sum::<f64>() / dithered.len() as f64;
+            assert!((mean - 100.3).abs() < 0.02, "{dither:?} mean {mean}");
+
+            // black and white are clamped, not pushed out of range
+            let extremes = vec![RGB::new(0.0, 1.0, f64::NAN); 64 * 64];
+            let dithered = RGB::to_srgb_array_dithered(&extremes, 64, dither);
+            assert!(dithered.chunks_exact(3).all(|c| c == [0, 255, 0]));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn dithering_needs_whole_rows() {
+        RGB::to_srgb_array_dithered(&[RGB::new(0.5, 0.5, 0.5); 5], 2, Dither::Ordered);
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::OnceLock;
+
+/// Noise added before quantizing to 8 bits, hides the banding of slow gradients
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Dither {
+    #[default]
+    None,
+    /// 8x8 Bayer matrix, cheap but leaves a visible cross-hatch pattern
+    Ordered,
+    /// 64x64 void-and-cluster mask, the error only has high frequencies so it reads as
+    /// fine grain
+    BlueNoise,
+}
+
+impl Dither {
+    /// Names accepted by [`Dither::from_name`]
+    pub const NAMES: [&'static str; 3] = ["none", "ordered", "blue-noise"];
+
+    pub fn from_name(name: &str) -> Option<Dither> {
+        match name {
+            "none" => Some(Dither::None),
+            "ordered" => Some(Dither::Ordered),
+            "blue-noise" => Some(Dither::BlueNoise),
+            _ => None,
+        }
+    }
+
+    /// Offset in [-0.5, 0.5) to add to a pixel scaled to [0, 255] before rounding
+    pub fn offset(&self, x: u32, y: u32) -> f64 {
+        match self {
+            Dither::None => 0.0,
+            Dither::Ordered => threshold(bayer_matrix(), BAYER_SIZE, x, y),
+            Dither::BlueNoise => threshold(blue_noise_mask(), BLUE_NOISE_SIZE, x, y),
+        }
+    }
+}
+
+/// Rank of the pixel in the tiled mask, mapped to the middle of its slot in [-0.5, 0.5)
+fn threshold(ranks: &[u16], size: usize, x: u32, y: u32) -> f64 {
+    let rank = ranks[(y as usize % size) * size + x as usize % size];
+    (rank as f64 + 0.5) / ranks.len() as f64 - 0.5
+}
+
+const BAYER_SIZE: usize = 8;
+
+fn bayer_matrix() -> &'static [u16] {
+    static MATRIX: OnceLock<Vec<u16>> = OnceLock::new();
+    MATRIX.get_or_init(|| {
+        let mut ranks = vec![0; BAYER_SIZE * BAYER_SIZE];
+        for y in 0..BAYER_SIZE {
+            for x in 0..BAYER_SIZE {
+                // interleave the bits of x ^ y and y, in reverse order
+                let xy = x ^ y;
+                let mut rank = 0;
+                for bit in 0..3 {
+                    rank = (rank << 2) | ((xy >> bit) & 1) << 1 | ((y >> bit) & 1);
+                }
+                ranks[y * BAYER_SIZE + x] = rank as u16;
+            }
+        }
+        ranks
+    })
+}
+
+const BLUE_NOISE_SIZE: usize = 64;
+
+fn blue_noise_mask() -> &'static [u16] {
+    static MASK: OnceLock<Vec<u16>> = OnceLock::new();
+    MASK.get_or_init(|| void_and_cluster(BLUE_NOISE_SIZE))
+}
+
+/// Ulichney's void-and-cluster method, ranks the pixels of a tileable size x size mask
+/// so that every threshold gives evenly spread points.
+///
+/// Each pixel that is on adds a gaussian to the energy of its neighbours, the tightest
+/// cluster is the pixel that is on with the most energy and the largest void the pixel
+/// that is off with the least.
+fn void_and_cluster(size: usize) -> Vec<u16> {
+    const SIGMA: f64 = 1.5;
+    const RADIUS: i64 = 6;
+    let count = size * size;
+
+    let kernel: Vec<(i64, i64, f64)> = (-RADIUS..=RADIUS)
+        .flat_map(|dy| (-RADIUS..=RADIUS).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| {
+            let energy = (-((dx * dx + dy * dy) as f64) / (2.0 * SIGMA * SIGMA)).exp();
+            (dx, dy, energy)
+        })
+        .collect();
+
+    let update = |energy: &mut [f64], index: usize, sign: f64| {
+        let (x, y) = ((index % size) as i64, (index / size) as i64);
+        for &(dx, dy, e) in &kernel {
+            let nx = (x + dx).rem_euclid(size as i64) as usize;
+            let ny = (y + dy).rem_euclid(size as i64) as usize;
+            energy[ny * size + nx] += sign * e;
+        }
+    };
+    let tightest_cluster = |pattern: &[bool], energy: &[f64]| {
+        (0..count)
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f64]| {
+        (0..count)
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // initial pattern, a tenth of the pixels picked by a xorshift generator
+    let mut pattern = vec![false; count];
+    let mut energy = vec![0.0; count];
+    let mut state: u32 = 0x9e37_79b9;
+    let mut ones = 0;
+    while ones < count / 10 {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let index = state as usize % count;
+        if !pattern[index] {
+            pattern[index] = true;
+            update(&mut energy, index, 1.0);
+            ones += 1;
+        }
+    }
+
+    // spread it out, moving the tightest cluster to the largest void until it stays put
+    loop {
+        let cluster = tightest_cluster(&pattern, &energy);
+        pattern[cluster] = false;
+        update(&mut energy, cluster, -1.0);
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        update(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; count];
+
+    // ranks below the initial pattern, removing clusters from a copy
+    let mut removed = pattern.clone();
+    let mut removed_energy = energy.clone();
+    for rank in (0..ones).rev() {
+        let cluster = tightest_cluster(&removed, &removed_energy);
+        removed[cluster] = false;
+        update(&mut removed_energy, cluster, -1.0);
+        ranks[cluster] = rank as u16;
+    }
+
+    // ranks above it, filling voids
+    for rank in ones..count {
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        update(&mut energy, void, 1.0);
+        ranks[void] = rank as u16;
+    }
+
+    ranks
+}
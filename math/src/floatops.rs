@@ -7,6 +7,8 @@ pub trait Float:
     + AddAssign
     + MulAssign
     + DivAssign
+    + Send
+    + Sync
 {
     fn constant(c: f32) -> Self {
         <Self as num::traits::cast::FromPrimitive>::from_f32(c).unwrap()
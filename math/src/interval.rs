@@ -1,5 +1,6 @@
 use crate::floatops::Float;
 
+#[derive(Clone, Copy)]
 pub struct Interval<T> {
     pub min: T,
     pub max: T,
@@ -24,8 +25,12 @@ where
         }
     }
 
-    pub fn default() -> Interval<T> {
-        Self::empty()
+    /// smallest interval that contains both intervals
+    pub fn surrounding(a: &Interval<T>, b: &Interval<T>) -> Interval<T> {
+        Interval {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
     }
 
     pub fn universe() -> Interval<T> {
@@ -37,6 +42,10 @@ where
 
     // check functions
 
+    pub fn size(&self) -> T {
+        self.max - self.min
+    }
+
     /// checks if a value is inside the range, inclusive
     /// use "surrounds" for exclusive check
     pub fn contains(&self, x: T) -> bool {
@@ -49,6 +58,12 @@ where
         self.min < x && x < self.max
     }
 
+    /// grows the interval by delta, half on each side
+    pub fn expand(&self, delta: T) -> Interval<T> {
+        let padding = delta * T::constant(0.5);
+        Interval::new(self.min - padding, self.max + padding)
+    }
+
     /// clamps a value inside the interval
     pub fn clamp(&self, x: T) -> T {
         if x < self.min {
@@ -60,3 +75,12 @@ where
         }
     }
 }
+
+impl<T> Default for Interval<T>
+where
+    T: Float,
+{
+    fn default() -> Interval<T> {
+        Self::empty()
+    }
+}
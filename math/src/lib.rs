@@ -1,12 +1,16 @@
+mod aabb;
 mod angles;
 mod colors;
+mod dither;
 mod floatops;
 mod interval;
 mod rays;
 mod vectors;
 
+pub use aabb::*;
 pub use angles::*;
 pub use colors::*;
+pub use dither::*;
 pub use floatops::Float;
 pub use interval::*;
 pub use rays::*;
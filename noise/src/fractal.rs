@@ -0,0 +1,121 @@
+use crate::Noise;
+
+/// Octave settings shared by the fractal combinators.
+///
+/// Each octave samples the noise at `lacunarity` times the frequency and `gain` times
+/// the amplitude of the previous one.
+#[derive(Clone, Copy)]
+struct Octaves {
+    count: u32,
+    lacunarity: f64,
+    gain: f64,
+}
+
+impl Octaves {
+    fn new(count: u32) -> Octaves {
+        Octaves {
+            count,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    /// Weighted average of `shape` applied to every octave, so the result stays in the
+    /// range of `shape`
+    fn sum<N: Noise>(&self, noise: &N, x: f64, y: f64, z: f64, shape: impl Fn(f64) -> f64) -> f64 {
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut total_amplitude = 0.0;
+        for _ in 0..self.count {
+            let value = noise.sample(x * frequency, y * frequency, z * frequency);
+            total += amplitude * shape(value);
+            total_amplitude += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+        if total_amplitude > 0.0 {
+            total / total_amplitude
+        } else {
+            0.0
+        }
+    }
+}
+
+macro_rules! impl_octaves {
+    ($name:ident) => {
+        impl<N> $name<N> {
+            /// Uses `count` octaves, each twice the frequency and half the amplitude of
+            /// the previous one
+            pub fn new(noise: N, count: u32) -> $name<N> {
+                $name {
+                    noise,
+                    octaves: Octaves::new(count),
+                }
+            }
+
+            /// Frequency multiplier between octaves
+            pub fn with_lacunarity(mut self, lacunarity: f64) -> $name<N> {
+                self.octaves.lacunarity = lacunarity;
+                self
+            }
+
+            /// Amplitude multiplier between octaves
+            pub fn with_gain(mut self, gain: f64) -> $name<N> {
+                self.octaves.gain = gain;
+                self
+            }
+        }
+    };
+}
+
+/// Fractional Brownian motion, octaves of the noise added together.
+/// Keeps the range of the noise, [-1, 1] for gradient noise.
+#[derive(Clone, Copy)]
+pub struct Fbm<N> {
+    noise: N,
+    octaves: Octaves,
+}
+
+impl_octaves!(Fbm);
+
+impl<N: Noise> Noise for Fbm<N> {
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        self.octaves.sum(&self.noise, x, y, z, |value| value)
+    }
+}
+
+/// Octaves of the absolute value of the noise, the creases where gradient noise crosses
+/// zero give a billowy look. Values are in [0, 1] for gradient noise.
+#[derive(Clone, Copy)]
+pub struct Turbulence<N> {
+    noise: N,
+    octaves: Octaves,
+}
+
+impl_octaves!(Turbulence);
+
+impl<N: Noise> Noise for Turbulence<N> {
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        self.octaves.sum(&self.noise, x, y, z, f64::abs)
+    }
+}
+
+/// Octaves of (1 - |noise|)^2, turning the zero crossings of gradient noise into sharp
+/// ridges like mountain crests. Values are in [0, 1] for gradient noise.
+#[derive(Clone, Copy)]
+pub struct Ridged<N> {
+    noise: N,
+    octaves: Octaves,
+}
+
+impl_octaves!(Ridged);
+
+impl<N: Noise> Noise for Ridged<N> {
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        self.octaves.sum(&self.noise, x, y, z, |value| {
+            let ridge = (1.0 - value.abs()).max(0.0);
+            ridge * ridge
+        })
+    }
+}
@@ -1,5 +1,29 @@
+mod fractal;
+mod perlin;
+mod simplex;
+mod worley;
+
+pub use fractal::*;
+pub use perlin::*;
+pub use simplex::*;
+pub use worley::*;
+
 use external::squirrel_noise::*;
 
+/// Deterministic 3D noise field, the same seed and point always give the same value
+pub trait Noise: Send + Sync {
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64;
+}
+
+impl<N> Noise for &N
+where
+    N: Noise + ?Sized,
+{
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        (**self).sample(x, y, z)
+    }
+}
+
 pub struct RandomNumberGenerator {
     pos: i32,
     seed: u32,
@@ -10,6 +34,17 @@ impl RandomNumberGenerator {
         RandomNumberGenerator { pos: 0, seed }
     }
 
+    /// Independent stream for one sample of one pixel.
+    ///
+    /// The stream only depends on its arguments, never on how many numbers other pixels
+    /// consumed, so any pixel can be re-rendered on its own and get the same noise.
+    pub fn for_pixel_sample(seed: u32, x: u32, y: u32, sample_index: u32) -> RandomNumberGenerator {
+        let seed = squirrel_noise5(x as i32, seed) as u32;
+        let seed = squirrel_noise5(y as i32, seed) as u32;
+        let seed = squirrel_noise5(sample_index as i32, seed) as u32;
+        RandomNumberGenerator::new(seed)
+    }
+
     pub fn next_bool_with_probability(&mut self, probability: f32) -> bool {
         self.next_f32() < probability
     }
@@ -40,3 +75,133 @@ impl RandomNumberGenerator {
         min + self.next_f64() * (max - min)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points spread over a few hundred cells, including negative coordinates
+    fn sample_points(count: usize) -> Vec<(f64, f64, f64)> {
+        let mut rng = RandomNumberGenerator::new(12345);
+        (0..count)
+            .map(|_| {
+                (
+                    rng.next_range_f64(-50.0, 50.0),
+                    rng.next_range_f64(-50.0, 50.0),
+                    rng.next_range_f64(-50.0, 50.0),
+                )
+            })
+            .collect()
+    }
+
+    fn assert_range<N: Noise>(noise: &N, min: f64, max: f64) {
+        for (x, y, z) in sample_points(20000) {
+            let value = noise.sample(x, y, z);
+            assert!(
+                (min..=max).contains(&value),
+                "{value} at ({x}, {y}, {z}) is outside [{min}, {max}]"
+            );
+        }
+    }
+
+    fn assert_deterministic<N: Noise>(make: impl Fn(u32) -> N) {
+        let (a, b, other) = (make(7), make(7), make(8));
+        let mut differences = 0;
+        for (x, y, z) in sample_points(1000) {
+            assert_eq!(a.sample(x, y, z), b.sample(x, y, z));
+            if a.sample(x, y, z) != other.sample(x, y, z) {
+                differences += 1;
+            }
+        }
+        assert!(differences > 900, "seeds 7 and 8 gave the same noise");
+    }
+
+    /// Compares values on both sides of the integer lattice planes and next to random
+    /// points, a jump there means neighbouring cells disagree
+    fn assert_continuous<N: Noise>(noise: &N) {
+        let epsilon = 1e-9;
+        let mut pairs = Vec::new();
+        for (x, y, z) in sample_points(2000) {
+            let (cx, cy, cz) = (x.round(), y.round(), z.round());
+            pairs.push(((cx - epsilon, y, z), (cx + epsilon, y, z)));
+            pairs.push(((x, cy - epsilon, z), (x, cy + epsilon, z)));
+            pairs.push(((x, y, cz - epsilon), (x, y, cz + epsilon)));
+            pairs.push((
+                (cx - epsilon, cy - epsilon, cz - epsilon),
+                (cx + epsilon, cy + epsilon, cz + epsilon),
+            ));
+            pairs.push(((x, y, z), (x + epsilon, y - epsilon, z + epsilon)));
+        }
+        for (p, q) in pairs {
+            let difference = (noise.sample(p.0, p.1, p.2) - noise.sample(q.0, q.1, q.2)).abs();
+            assert!(
+                difference < 1e-6,
+                "jump of {difference} between {p:?} and {q:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn perlin() {
+        assert_range(&Perlin::new(1), -1.0, 1.0);
+        assert_deterministic(Perlin::new);
+        assert_continuous(&Perlin::new(1));
+        for (x, y, z) in [(0.0, 0.0, 0.0), (3.0, -7.0, 12.0), (-1.0, -1.0, -1.0)] {
+            assert_eq!(Perlin::new(1).sample(x, y, z), 0.0);
+        }
+    }
+
+    #[test]
+    fn simplex() {
+        assert_range(&Simplex::new(1), -1.0, 1.0);
+        assert_deterministic(Simplex::new);
+        assert_continuous(&Simplex::new(1));
+    }
+
+    #[test]
+    fn worley() {
+        // one feature point per cell, the closest one is never more than a cell diagonal away
+        let diagonal = 3f64.sqrt();
+        assert_range(&Worley::new(1), 0.0, diagonal);
+        assert_range(
+            &Worley::new(1).with_feature(WorleyFeature::F2),
+            0.0,
+            2.0 * diagonal,
+        );
+        assert_range(
+            &Worley::new(1).with_feature(WorleyFeature::F2MinusF1),
+            0.0,
+            2.0 * diagonal,
+        );
+        assert_deterministic(Worley::new);
+        assert_deterministic(|seed| Worley::new(seed).with_feature(WorleyFeature::F2MinusF1));
+        assert_continuous(&Worley::new(1));
+
+        let f1 = Worley::new(1);
+        let f2 = Worley::new(1).with_feature(WorleyFeature::F2);
+        for (x, y, z) in sample_points(1000) {
+            assert!(f1.sample(x, y, z) <= f2.sample(x, y, z));
+        }
+    }
+
+    #[test]
+    fn fractals() {
+        assert_range(&Fbm::new(Perlin::new(1), 5), -1.0, 1.0);
+        assert_range(&Turbulence::new(Simplex::new(1), 5), 0.0, 1.0);
+        assert_range(&Ridged::new(Perlin::new(1), 5), 0.0, 1.0);
+        assert_range(&Fbm::new(Simplex::new(1), 0), 0.0, 0.0);
+        assert_deterministic(|seed| Fbm::new(Perlin::new(seed), 4).with_lacunarity(2.5));
+        assert_deterministic(|seed| Turbulence::new(Simplex::new(seed), 4));
+        assert_deterministic(|seed| Ridged::new(Perlin::new(seed), 4).with_gain(0.6));
+        assert_continuous(&Fbm::new(Perlin::new(1), 4));
+        assert_continuous(&Turbulence::new(Simplex::new(1), 4));
+        assert_continuous(&Ridged::new(Perlin::new(1), 4));
+
+        // a single octave is the noise itself
+        let perlin = Perlin::new(3);
+        let fbm = Fbm::new(perlin, 1);
+        for (x, y, z) in sample_points(100) {
+            assert_eq!(fbm.sample(x, y, z), perlin.sample(x, y, z));
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use external::squirrel_noise::get3d_noise_uint;
+
+use crate::Noise;
+
+/// Improved gradient noise (Perlin 2002).
+///
+/// The gradient at each lattice point is picked by hashing its coordinates with the seed,
+/// so there is no permutation table and the noise does not repeat. Values are roughly in
+/// [-1, 1] and are 0 on the integer lattice.
+#[derive(Clone, Copy)]
+pub struct Perlin {
+    seed: u32,
+}
+
+impl Perlin {
+    pub fn new(seed: u32) -> Perlin {
+        Perlin { seed }
+    }
+}
+
+impl Noise for Perlin {
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+        let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            let hash = get3d_noise_uint(
+                ix.wrapping_add(dx),
+                iy.wrapping_add(dy),
+                iz.wrapping_add(dz),
+                self.seed,
+            );
+            gradient_dot(hash, fx - dx as f64, fy - dy as f64, fz - dz as f64)
+        };
+
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+}
+
+/// Dot product of (x, y, z) with one of the 12 directions to the edges of a cube
+pub(crate) fn gradient_dot(hash: u32, x: f64, y: f64, z: f64) -> f64 {
+    match hash % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+/// 6t^5 - 15t^4 + 10t^3, its first and second derivatives are 0 at both ends
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
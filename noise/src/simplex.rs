@@ -0,0 +1,72 @@
+use external::squirrel_noise::get3d_noise_uint;
+
+use crate::{perlin::gradient_dot, Noise};
+
+/// Simplex noise (Perlin 2001, after Gustavson's reference implementation).
+///
+/// Sums the contributions of the 4 corners of the tetrahedron around the point instead
+/// of the 8 corners of a cube, which is cheaper and has fewer axis-aligned artifacts
+/// than [`crate::Perlin`]. Values are roughly in [-1, 1].
+#[derive(Clone, Copy)]
+pub struct Simplex {
+    seed: u32,
+}
+
+impl Simplex {
+    pub fn new(seed: u32) -> Simplex {
+        Simplex { seed }
+    }
+}
+
+const SKEW: f64 = 1.0 / 3.0;
+const UNSKEW: f64 = 1.0 / 6.0;
+
+impl Noise for Simplex {
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        // find the cell of the skewed grid, made of 6 tetrahedrons
+        let s = (x + y + z) * SKEW;
+        let (i, j, k) = ((x + s).floor(), (y + s).floor(), (z + s).floor());
+        let t = (i + j + k) * UNSKEW;
+        let (x0, y0, z0) = (x - (i - t), y - (j - t), z - (k - t));
+
+        // the tetrahedron depends on the order of the offsets
+        let (first, second) = if x0 >= y0 {
+            if y0 >= z0 {
+                ((1, 0, 0), (1, 1, 0))
+            } else if x0 >= z0 {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if y0 < z0 {
+            ((0, 0, 1), (0, 1, 1))
+        } else if x0 < z0 {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+
+        let (i, j, k) = (i as i32, j as i32, k as i32);
+        let corner = |(di, dj, dk): (i32, i32, i32), offset: f64| {
+            let x = x0 - di as f64 + offset;
+            let y = y0 - dj as f64 + offset;
+            let z = z0 - dk as f64 + offset;
+            let falloff = 0.6 - x * x - y * y - z * z;
+            if falloff < 0.0 {
+                return 0.0;
+            }
+            let hash = get3d_noise_uint(
+                i.wrapping_add(di),
+                j.wrapping_add(dj),
+                k.wrapping_add(dk),
+                self.seed,
+            );
+            falloff.powi(4) * gradient_dot(hash, x, y, z)
+        };
+
+        32.0 * (corner((0, 0, 0), 0.0)
+            + corner(first, UNSKEW)
+            + corner(second, 2.0 * UNSKEW)
+            + corner((1, 1, 1), 3.0 * UNSKEW))
+    }
+}
@@ -0,0 +1,77 @@
+use external::squirrel_noise::get4d_noise_zero_to_one_f64;
+
+use crate::Noise;
+
+/// Which distance cellular noise returns
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WorleyFeature {
+    /// to the closest feature point, round cells
+    F1,
+    /// to the second closest feature point
+    F2,
+    /// F2 - F1, 0 on the borders between cells
+    F2MinusF1,
+}
+
+/// Cellular noise (Worley 1996).
+///
+/// Every unit cube holds one feature point at a position hashed from its coordinates and
+/// the seed. Values are distances in cell units, mostly between 0 and 1.
+#[derive(Clone, Copy)]
+pub struct Worley {
+    seed: u32,
+    feature: WorleyFeature,
+}
+
+impl Worley {
+    /// Returns F1
+    pub fn new(seed: u32) -> Worley {
+        Worley {
+            seed,
+            feature: WorleyFeature::F1,
+        }
+    }
+
+    pub fn with_feature(mut self, feature: WorleyFeature) -> Worley {
+        self.feature = feature;
+        self
+    }
+}
+
+impl Noise for Worley {
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (ix, iy, iz) = (x.floor() as i32, y.floor() as i32, z.floor() as i32);
+
+        // squared distances to the two closest feature points
+        let mut f1 = f64::INFINITY;
+        let mut f2 = f64::INFINITY;
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let (cx, cy, cz) = (
+                        ix.wrapping_add(dx),
+                        iy.wrapping_add(dy),
+                        iz.wrapping_add(dz),
+                    );
+                    let jitter = |axis| get4d_noise_zero_to_one_f64(cx, cy, cz, axis, self.seed);
+                    let px = cx as f64 + jitter(0) - x;
+                    let py = cy as f64 + jitter(1) - y;
+                    let pz = cz as f64 + jitter(2) - z;
+                    let distance = px * px + py * py + pz * pz;
+                    if distance < f1 {
+                        f2 = f1;
+                        f1 = distance;
+                    } else if distance < f2 {
+                        f2 = distance;
+                    }
+                }
+            }
+        }
+
+        match self.feature {
+            WorleyFeature::F1 => f1.sqrt(),
+            WorleyFeature::F2 => f2.sqrt(),
+            WorleyFeature::F2MinusF1 => f2.sqrt() - f1.sqrt(),
+        }
+    }
+}
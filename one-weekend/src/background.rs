@@ -0,0 +1,160 @@
+use math::{Float, Vector3, RGB};
+use num::traits::AsPrimitive;
+
+use crate::{Image, Wrap};
+
+/// Light coming from infinitely far away, seen by rays that miss the world
+pub trait Background<T, U>: Send + Sync {
+    /// `direction` is a unit vector
+    fn color(&self, direction: Vector3<T>) -> RGB<U>;
+}
+
+/// Same colour in every direction, black turns the scene into an interior lit only by
+/// its emitters
+pub struct SolidBackground<U>(RGB<U>);
+
+impl<U> SolidBackground<U> {
+    pub fn new(color: RGB<U>) -> Self {
+        Self(color)
+    }
+}
+
+impl<T, U> Background<T, U> for SolidBackground<U>
+where
+    U: Float,
+{
+    fn color(&self, _: Vector3<T>) -> RGB<U> {
+        self.0
+    }
+}
+
+/// Vertical blend between two colours, from straight down to straight up
+pub struct GradientBackground<U> {
+    bottom: RGB<U>,
+    top: RGB<U>,
+}
+
+impl<U> GradientBackground<U>
+where
+    U: Float,
+{
+    pub fn new(bottom: RGB<U>, top: RGB<U>) -> Self {
+        Self { bottom, top }
+    }
+
+    /// White to light blue
+    pub fn sky() -> Self {
+        Self::new(
+            RGB::white(),
+            RGB::new(U::constant(0.5), U::constant(0.7), U::constant(1.0)),
+        )
+    }
+}
+
+impl<T, U> Background<T, U> for GradientBackground<U>
+where
+    T: Float + AsPrimitive<U>,
+    U: 'static + Float,
+{
+    fn color(&self, direction: Vector3<T>) -> RGB<U> {
+        let a: U = ((direction.y + T::constant(1.0)) * T::constant(0.5)).as_();
+        self.bottom * (U::constant(1.0) - a) + self.top * a
+    }
+}
+
+/// Latitude-longitude (equirectangular) environment image.
+///
+/// The middle row is the horizon, the top row looks straight up, and the center of the
+/// image looks down -Z.
+pub struct EnvironmentMap<U> {
+    image: Image<U>,
+}
+
+impl<U> EnvironmentMap<U> {
+    pub fn new(image: Image<U>) -> Self {
+        Self { image }
+    }
+}
+
+impl<T, U> Background<T, U> for EnvironmentMap<U>
+where
+    T: Float + AsPrimitive<U>,
+    U: 'static + Float,
+{
+    fn color(&self, direction: Vector3<T>) -> RGB<U> {
+        let two_pi = T::constant(2.0) * T::PI();
+        let u = direction.x.atan2(-direction.z) / two_pi + T::constant(0.5);
+        let v = direction.y.max(-T::one()).min(T::one()).asin() / T::PI() + T::constant(0.5);
+        self.image.sample(u, v, Wrap::Repeat)
+    }
+}
+
+/// Cheap analytic daylight: a sky gradient from horizon to zenith, a flat ground colour
+/// below the horizon and a sun disc with a soft glow around it.
+pub struct ProceduralSky<T, U> {
+    sun_direction: Vector3<T>,
+    cos_sun_radius: T,
+    sun_color: RGB<U>,
+    zenith: RGB<U>,
+    horizon: RGB<U>,
+    ground: RGB<U>,
+}
+
+impl<T, U> ProceduralSky<T, U>
+where
+    T: Float,
+    U: Float,
+{
+    /// Clear day sky with the sun in the given direction
+    pub fn new(sun_direction: Vector3<T>) -> Self {
+        Self {
+            sun_direction: sun_direction.unit_vector(),
+            // the sun is about half a degree wide
+            cos_sun_radius: T::constant(0.25).to_radians().cos(),
+            sun_color: RGB::new(U::constant(1.0), U::constant(0.95), U::constant(0.85))
+                * U::constant(50.0),
+            zenith: RGB::new(U::constant(0.15), U::constant(0.35), U::constant(0.8)),
+            horizon: RGB::new(U::constant(0.75), U::constant(0.85), U::constant(1.0)),
+            ground: RGB::new(U::constant(0.3), U::constant(0.28), U::constant(0.25)),
+        }
+    }
+
+    pub fn with_colors(mut self, zenith: RGB<U>, horizon: RGB<U>, ground: RGB<U>) -> Self {
+        self.zenith = zenith;
+        self.horizon = horizon;
+        self.ground = ground;
+        self
+    }
+
+    /// Radiance of the sun disc, the glow around it scales with it
+    pub fn with_sun_color(mut self, sun_color: RGB<U>) -> Self {
+        self.sun_color = sun_color;
+        self
+    }
+}
+
+impl<T, U> Background<T, U> for ProceduralSky<T, U>
+where
+    T: Float + AsPrimitive<U>,
+    U: 'static + Float,
+{
+    fn color(&self, direction: Vector3<T>) -> RGB<U> {
+        if direction.y < T::constant(0.0) {
+            // fade quickly from the horizon into the ground
+            let a: U = (-direction.y * T::constant(10.0)).min(T::one()).as_();
+            return self.horizon * (U::one() - a) + self.ground * a;
+        }
+
+        let a: U = direction.y.sqrt().as_();
+        let sky = self.horizon * (U::one() - a) + self.zenith * a;
+
+        let cos_angle = direction.dot(self.sun_direction);
+        let sun = if cos_angle >= self.cos_sun_radius {
+            self.sun_color
+        } else {
+            let glow: U = cos_angle.max(T::constant(0.0)).powi(256).as_();
+            self.sun_color * (glow * U::constant(0.05))
+        };
+        sky + sun
+    }
+}
@@ -0,0 +1,458 @@
+use math::Aabb;
+use math::Float;
+use math::Interval;
+use math::Point3;
+use math::Ray;
+
+use crate::HitRecord;
+use crate::Hittable;
+
+/// Number of buckets used to evaluate the surface area heuristic on each axis
+const SAH_BUCKETS: usize = 12;
+/// Cost of visiting an interior node, relative to intersecting a primitive
+const TRAVERSAL_COST: f32 = 0.125;
+const MAX_PRIMITIVES_IN_LEAF: usize = 4;
+/// Past this depth nodes are split at the median so the tree stays within the traversal stack
+const MAX_SAH_DEPTH: usize = 64;
+const TRAVERSAL_STACK_SIZE: usize = 128;
+
+enum BvhNode<T> {
+    Leaf {
+        bbox: Aabb<T>,
+        first: usize,
+        count: usize,
+    },
+    // the left child is always the next node in the array
+    Interior {
+        bbox: Aabb<T>,
+        right: usize,
+        axis: u8,
+    },
+}
+
+impl<T> BvhNode<T> {
+    fn bbox(&self) -> &Aabb<T> {
+        match self {
+            BvhNode::Leaf { bbox, .. } => bbox,
+            BvhNode::Interior { bbox, .. } => bbox,
+        }
+    }
+}
+
+/// Flattened hierarchy over a set of bounding boxes, indexed by position.
+///
+/// It only knows about boxes, so it can be shared by anything that needs to find the
+/// closest of many primitives: the generic `Bvh` below and triangle meshes.
+pub(crate) struct BvhTree<T> {
+    nodes: Vec<BvhNode<T>>,
+    indices: Vec<usize>,
+}
+
+impl<T> BvhTree<T>
+where
+    T: Float,
+{
+    pub(crate) fn new(bounds: &[Aabb<T>]) -> BvhTree<T> {
+        let centroids: Vec<Point3<T>> = bounds.iter().map(|b| b.centroid()).collect();
+        let mut tree = BvhTree {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            indices: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            let mut indices = std::mem::take(&mut tree.indices);
+            tree.build(bounds, &centroids, &mut indices, 0, 0);
+            tree.indices = indices;
+        }
+        tree
+    }
+
+    pub(crate) fn bounding_box(&self) -> Aabb<T> {
+        match self.nodes.first() {
+            Some(node) => *node.bbox(),
+            None => Aabb::empty(),
+        }
+    }
+
+    /// Finds the closest hit, `hit_primitive` is called with the index of each candidate
+    /// primitive and the interval still left to search.
+    pub(crate) fn hit<'a, U, F>(
+        &self,
+        ray: &Ray<T>,
+        ray_t: Interval<T>,
+        mut hit_primitive: F,
+    ) -> Option<HitRecord<'a, T, U>>
+    where
+        F: FnMut(usize, Interval<T>) -> Option<HitRecord<'a, T, U>>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let direction = ray.direction();
+        let mut result = None;
+        let mut closest = ray_t.max;
+        let mut stack = [0usize; TRAVERSAL_STACK_SIZE];
+        let mut stack_size = 1;
+
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node_index = stack[stack_size];
+            let node = &self.nodes[node_index];
+            let ray_t = Interval::new(ray_t.min, closest);
+            if !node.bbox().hit(ray, ray_t) {
+                continue;
+            }
+            match node {
+                BvhNode::Leaf { first, count, .. } => {
+                    for &index in &self.indices[*first..*first + *count] {
+                        if let Some(hit) = hit_primitive(index, Interval::new(ray_t.min, closest)) {
+                            closest = hit.t;
+                            result = Some(hit);
+                        }
+                    }
+                }
+                BvhNode::Interior { right, axis, .. } => {
+                    // visit the child closer to the ray origin first
+                    let left = node_index + 1;
+                    let (near, far) = if direction[*axis] < T::constant(0.0) {
+                        (*right, left)
+                    } else {
+                        (left, *right)
+                    };
+                    stack[stack_size] = far;
+                    stack[stack_size + 1] = near;
+                    stack_size += 2;
+                }
+            }
+        }
+        result
+    }
+
+    fn build(
+        &mut self,
+        bounds: &[Aabb<T>],
+        centroids: &[Point3<T>],
+        indices: &mut [usize],
+        first: usize,
+        depth: usize,
+    ) {
+        let bbox = indices.iter().fold(Aabb::empty(), |bbox, &i| {
+            Aabb::surrounding(&bbox, &bounds[i])
+        });
+        let count = indices.len();
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode::Leaf { bbox, first, count });
+        if count == 1 {
+            return;
+        }
+
+        let centroid_bounds = Aabb::surrounding_points(indices.iter().map(|&i| centroids[i]));
+
+        let split = if depth < MAX_SAH_DEPTH {
+            Self::find_sah_split(bounds, centroids, indices, &bbox, &centroid_bounds)
+        } else {
+            None
+        };
+
+        let (axis, mid) = match split {
+            Some(SahSplit::Leaf) => return,
+            Some(SahSplit::Bucket { axis, bucket }) => {
+                let mid = partition(indices, |&i| {
+                    bucket_index(&centroid_bounds, axis, &centroids[i]) <= bucket
+                });
+                (axis, mid)
+            }
+            None => (centroid_bounds.longest_axis(), 0),
+        };
+
+        // buckets can fail to separate centroids that are very close, fall back to the median
+        let mid = if mid == 0 || mid == count {
+            indices.select_nth_unstable_by(count / 2, |&a, &b| {
+                centroids[a][axis]
+                    .partial_cmp(&centroids[b][axis])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            count / 2
+        } else {
+            mid
+        };
+
+        let (left, right) = indices.split_at_mut(mid);
+        self.build(bounds, centroids, left, first, depth + 1);
+        let right_index = self.nodes.len();
+        self.build(bounds, centroids, right, first + mid, depth + 1);
+        self.nodes[node_index] = BvhNode::Interior {
+            bbox,
+            right: right_index,
+            axis,
+        };
+    }
+
+    fn find_sah_split(
+        bounds: &[Aabb<T>],
+        centroids: &[Point3<T>],
+        indices: &[usize],
+        bbox: &Aabb<T>,
+        centroid_bounds: &Aabb<T>,
+    ) -> Option<SahSplit> {
+        let count = indices.len();
+        let mut best: Option<(T, u8, usize)> = None;
+
+        for axis in 0..3u8 {
+            if centroid_bounds[axis].size() <= T::constant(0.0) {
+                continue;
+            }
+
+            let mut bucket_counts = [0usize; SAH_BUCKETS];
+            let mut bucket_bounds = [Aabb::empty(); SAH_BUCKETS];
+            for &i in indices {
+                let b = bucket_index(centroid_bounds, axis, &centroids[i]);
+                bucket_counts[b] += 1;
+                bucket_bounds[b] = Aabb::surrounding(&bucket_bounds[b], &bounds[i]);
+            }
+
+            // sweep from the right to get the cost of everything above each split
+            let mut right_costs = [T::constant(0.0); SAH_BUCKETS];
+            let mut right_count = 0;
+            let mut right_bounds = Aabb::empty();
+            for b in (1..SAH_BUCKETS).rev() {
+                right_count += bucket_counts[b];
+                right_bounds = Aabb::surrounding(&right_bounds, &bucket_bounds[b]);
+                right_costs[b - 1] = from_usize::<T>(right_count) * right_bounds.surface_area();
+            }
+
+            let mut left_count = 0;
+            let mut left_bounds = Aabb::empty();
+            for b in 0..SAH_BUCKETS - 1 {
+                left_count += bucket_counts[b];
+                left_bounds = Aabb::surrounding(&left_bounds, &bucket_bounds[b]);
+                let cost =
+                    from_usize::<T>(left_count) * left_bounds.surface_area() + right_costs[b];
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, b));
+                }
+            }
+        }
+
+        let (cost, axis, bucket) = best?;
+
+        // costs are kept unnormalized, so scale the fixed terms by the parent area
+        let area = bbox.surface_area();
+        let split_cost = T::constant(TRAVERSAL_COST) * area + cost;
+        let leaf_cost = from_usize::<T>(count) * area;
+        if count <= MAX_PRIMITIVES_IN_LEAF && leaf_cost <= split_cost {
+            Some(SahSplit::Leaf)
+        } else {
+            Some(SahSplit::Bucket { axis, bucket })
+        }
+    }
+}
+
+enum SahSplit {
+    Leaf,
+    Bucket { axis: u8, bucket: usize },
+}
+
+fn from_usize<T: Float>(n: usize) -> T {
+    T::from_usize(n).unwrap()
+}
+
+fn bucket_index<T: Float>(centroid_bounds: &Aabb<T>, axis: u8, centroid: &Point3<T>) -> usize {
+    let interval = &centroid_bounds[axis];
+    let relative = (centroid[axis] - interval.min) / interval.size();
+    let b = (relative * from_usize::<T>(SAH_BUCKETS))
+        .to_usize()
+        .unwrap_or(0);
+    b.min(SAH_BUCKETS - 1)
+}
+
+/// Moves the elements matching the predicate to the front, returns how many there are
+fn partition<F: Fn(&usize) -> bool>(indices: &mut [usize], predicate: F) -> usize {
+    let mut mid = 0;
+    for i in 0..indices.len() {
+        if predicate(&indices[i]) {
+            indices.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+/// Bounding volume hierarchy over a list of primitives, split with the surface area heuristic
+pub struct Bvh<T, P> {
+    tree: BvhTree<T>,
+    primitives: Vec<P>,
+}
+
+impl<T, P> Bvh<T, P>
+where
+    T: Float,
+{
+    pub fn new<U>(primitives: Vec<P>) -> Bvh<T, P>
+    where
+        P: Hittable<T, U>,
+    {
+        let bounds: Vec<Aabb<T>> = primitives.iter().map(|p| p.bounding_box()).collect();
+        Bvh {
+            tree: BvhTree::new(&bounds),
+            primitives,
+        }
+    }
+
+    pub fn primitives(&self) -> &[P] {
+        &self.primitives
+    }
+}
+
+impl<T, U, P> Hittable<T, U> for Bvh<T, P>
+where
+    T: Float,
+    P: Hittable<T, U>,
+{
+    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<'_, T, U>> {
+        self.tree.hit(ray, ray_t, |index, ray_t| {
+            self.primitives[index].hit(ray, ray_t)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb<T> {
+        self.tree.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use math::Vector3;
+    use noise::RandomNumberGenerator;
+
+    use super::*;
+    use crate::{GeometricPrimitive, Lambertian, Material, Sphere};
+
+    fn tree_depth<T>(tree: &BvhTree<T>, node: usize) -> usize {
+        match &tree.nodes[node] {
+            BvhNode::Leaf { .. } => 1,
+            BvhNode::Interior { right, .. } => {
+                1 + tree_depth(tree, node + 1).max(tree_depth(tree, *right))
+            }
+        }
+    }
+
+    fn random_spheres(seed: u32, count: usize) -> Vec<GeometricPrimitive<f64, f32>> {
+        let mut rng = RandomNumberGenerator::new(seed);
+        let material: Arc<dyn Material<f64, f32>> =
+            Arc::new(Lambertian::new(math::RGB::new(0.5, 0.5, 0.5)));
+        (0..count)
+            .map(|_| {
+                let center = Point3::new(
+                    rng.next_range_f64(-10.0, 10.0),
+                    rng.next_range_f64(-10.0, 10.0),
+                    rng.next_range_f64(-10.0, 10.0),
+                );
+                let radius = rng.next_range_f64(0.05, 1.0);
+                GeometricPrimitive::Sphere(Sphere::new(center, radius, material.clone()))
+            })
+            .collect()
+    }
+
+    fn random_ray(rng: &mut RandomNumberGenerator) -> Ray<f64> {
+        let origin = Point3::new(
+            rng.next_range_f64(-15.0, 15.0),
+            rng.next_range_f64(-15.0, 15.0),
+            rng.next_range_f64(-15.0, 15.0),
+        );
+        let direction = Vector3::new(
+            rng.next_range_f64(-1.0, 1.0),
+            rng.next_range_f64(-1.0, 1.0),
+            rng.next_range_f64(-1.0, 1.0),
+        );
+        Ray::new(origin, direction)
+    }
+
+    #[test]
+    fn hits_match_a_linear_scan() {
+        for (seed, count) in [(1, 1), (2, 7), (3, 100), (4, 1000)] {
+            let spheres = random_spheres(seed, count);
+            let bvh: Bvh<f64, GeometricPrimitive<f64, f32>> = Bvh::new(random_spheres(seed, count));
+            let linear = spheres.as_slice();
+            let mut rng = RandomNumberGenerator::new(seed + 100);
+            let mut hits = 0;
+            for _ in 0..2000 {
+                let ray = random_ray(&mut rng);
+                let ray_t = Interval::new(0.001, f64::INFINITY);
+                let expected = Hittable::<f64, f32>::hit(&linear, &ray, ray_t);
+                let actual = Hittable::<f64, f32>::hit(&bvh, &ray, ray_t);
+                match (expected, actual) {
+                    (None, None) => {}
+                    (Some(e), Some(a)) => {
+                        assert_eq!(e.t, a.t);
+                        hits += 1;
+                    }
+                    (e, a) => panic!(
+                        "seed {seed}: linear scan hit {}, bvh hit {}",
+                        e.is_some(),
+                        a.is_some()
+                    ),
+                }
+            }
+            if count > 1 {
+                assert!(hits > 0, "seed {seed}: no ray hit anything");
+            }
+        }
+    }
+
+    #[test]
+    fn empty_bvh_is_never_hit() {
+        let bvh: Bvh<f64, GeometricPrimitive<f64, f32>> = Bvh::new(Vec::new());
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        assert!(Hittable::<f64, f32>::hit(&bvh, &ray, Interval::universe()).is_none());
+        assert!(Hittable::<f64, f32>::bounding_box(&bvh).is_empty());
+    }
+
+    /// Boxes at exponentially growing distances make every SAH split peel off only a
+    /// few of them, which would give a tree as deep as the input is long
+    fn exponential_boxes(count: usize) -> Vec<Aabb<f64>> {
+        (0..count)
+            .map(|i| {
+                let x = 1.5f64.powi(i as i32);
+                Aabb::from_points(Point3::new(x, 0.0, 0.0), Point3::new(x * 1.01, 1.0, 1.0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sah_depth_is_limited() {
+        let bounds = exponential_boxes(1000);
+        let tree = BvhTree::new(&bounds);
+        let depth = tree_depth(&tree, 0);
+        // the SAH alone goes past the limit, the median splits below it add at most log2(n)
+        assert!(depth > MAX_SAH_DEPTH);
+        assert!(depth <= MAX_SAH_DEPTH + 11);
+    }
+
+    #[test]
+    fn deep_tree_fits_the_traversal_stack() {
+        let bounds = exponential_boxes(1000);
+        let tree = BvhTree::new(&bounds);
+        // each level leaves at most one far child on the stack
+        assert!(tree_depth(&tree, 0) < TRAVERSAL_STACK_SIZE);
+
+        // a ray along the row of boxes has to visit every one of them
+        for direction in [1.0, -1.0] {
+            let origin = if direction > 0.0 { -1.0 } else { 1e200 };
+            let ray = Ray::new(
+                Point3::new(origin, 0.5, 0.5),
+                Vector3::new(direction, 0.0, 0.0),
+            );
+            let mut visited = vec![false; bounds.len()];
+            let result: Option<HitRecord<'_, f64, f32>> =
+                tree.hit(&ray, Interval::new(0.0, f64::INFINITY), |index, _| {
+                    visited[index] = true;
+                    None
+                });
+            assert!(result.is_none());
+            assert!(visited.iter().all(|&v| v));
+        }
+    }
+}
@@ -1,55 +1,369 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+
 use math::*;
 use noise::RandomNumberGenerator;
 use num::traits::AsPrimitive;
 
-use crate::{Hittable, RNGAdapter};
+use crate::{Background, Hittable, RNGAdapter};
 
-pub struct Camera<T> {
-    center: Point3<T>,
-    pixel00_loc: Point3<T>,
-    pixel_delta_u: Vector3<T>,
-    pixel_delta_v: Vector3<T>,
-    defocus_disc_u: Vector3<T>,
-    defocus_disc_v: Vector3<T>,
-    image_width: u32,
-    image_height: u32,
-    samples_per_pixel: u32,
-    max_depth: u32,
+/// Side, in pixels, of the square tiles the image is split into for rendering
+const TILE_SIZE: u32 = 32;
+
+/// How directions around the camera are laid out on the image
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Projection {
+    /// Pinhole or thin lens, straight lines stay straight
+    #[default]
+    Perspective,
+    /// Parallel rays along the view direction, the image covers what the perspective
+    /// view covers at the focus distance
+    Orthographic,
+    /// Equidistant fisheye, the angle from the view direction grows linearly with the
+    /// distance from the center of the image. The vertical field of view, up to 360
+    /// degrees, spans the height of the image and pixels beyond it are black.
+    Fisheye,
+    /// Longitude across the width and latitude down the height of the image, the whole
+    /// sphere of directions with the view direction in the middle. Meant for 2:1 images.
+    Equirectangular,
 }
 
-impl<T> Camera<T>
+impl Projection {
+    /// Names accepted by [`Projection::from_name`]
+    pub const NAMES: [&'static str; 4] =
+        ["perspective", "orthographic", "fisheye", "equirectangular"];
+
+    pub fn from_name(name: &str) -> Option<Projection> {
+        match name {
+            "perspective" => Some(Projection::Perspective),
+            "orthographic" => Some(Projection::Orthographic),
+            "fisheye" => Some(Projection::Fisheye),
+            "equirectangular" => Some(Projection::Equirectangular),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        Projection::NAMES[*self as usize]
+    }
+}
+
+/// Where the camera stands and how its lens is set up, everything a [`CameraBuilder`]
+/// needs besides the size and sampling of the image
+#[derive(Clone, Copy)]
+pub struct CameraSettings<T> {
+    pub lookfrom: Point3<T>,
+    pub lookat: Point3<T>,
+    pub up: Vector3<T>,
+    /// Vertical field of view
+    pub vfov: Degrees<T>,
+    /// Angle of the cone of rays through each pixel, 0 keeps everything in focus
+    pub defocus_angle: Degrees<T>,
+    /// Distance from `lookfrom` to the plane in perfect focus
+    pub focus_dist: T,
+    pub projection: Projection,
+}
+
+impl<T: Float> Default for CameraSettings<T> {
+    /// At the origin looking down -Z, 90 degrees perspective and no defocus blur
+    fn default() -> Self {
+        CameraSettings {
+            lookfrom: Point3::new(T::constant(0.0), T::constant(0.0), T::constant(0.0)),
+            lookat: Point3::new(T::constant(0.0), T::constant(0.0), T::constant(-1.0)),
+            up: Vector3::new(T::constant(0.0), T::constant(1.0), T::constant(0.0)),
+            vfov: Degrees(T::constant(90.0)),
+            defocus_angle: Degrees(T::constant(0.0)),
+            focus_dist: T::constant(10.0),
+            projection: Projection::Perspective,
+        }
+    }
+}
+
+impl<T> CameraSettings<T>
 where
     T: Float + AsPrimitive<u32>,
     u32: AsPrimitive<T>,
 {
-    pub fn new(
-        aspect_ratio: T,
+    pub fn camera(
+        &self,
         image_width: u32,
+        image_height: u32,
         samples_per_pixel: u32,
         max_depth: u32,
-        vfov: Degrees<T>,
-        lookfrom: Point3<T>,
-        lookat: Point3<T>,
-        up: Vector3<T>,
-        defocus_angle: Degrees<T>,
-        focus_dist: T,
-    ) -> Camera<T> {
-        // Calculate the image height, and ensure that it's at least 1
-        let mut image_height = (image_width.as_() / aspect_ratio).as_();
-        if image_height < 1 {
-            image_height = 1;
-        }
-        let image_height = image_height;
-
-        // Determine viewport dimensions
-        let theta = vfov.0.to_radians();
+    ) -> Result<Camera<T>, CameraError> {
+        Camera::builder()
+            .image_width(image_width)
+            .image_height(image_height)
+            .samples_per_pixel(samples_per_pixel)
+            .max_depth(max_depth)
+            .vfov(self.vfov)
+            .lookfrom(self.lookfrom)
+            .lookat(self.lookat)
+            .up(self.up)
+            .defocus_angle(self.defocus_angle)
+            .focus_dist(self.focus_dist)
+            .projection(self.projection)
+            .build()
+    }
+}
+
+/// Camera configuration that [`CameraBuilder::build`] rejects
+#[derive(Debug, Clone, PartialEq)]
+pub enum CameraError {
+    /// The image is 0 pixels wide or high, also when the aspect ratio rounds the height
+    /// down to 0
+    EmptyImage {
+        width: u32,
+        height: u32,
+    },
+    /// The aspect ratio is not a positive number
+    AspectRatio(f64),
+    NoSamples,
+    /// The vertical field of view in degrees is not above 0 and below 180, or up to 360
+    /// for a fisheye
+    FieldOfView {
+        vfov: f64,
+        max: f64,
+    },
+    /// `lookfrom` and `lookat` are the same point
+    NoViewDirection,
+    /// `up` is zero or parallel to the view direction
+    UpParallelToView,
+    /// The defocus angle in degrees is not in [0, 180)
+    DefocusAngle(f64),
+    /// A defocus angle was given for a projection without a lens, anything but
+    /// perspective
+    DefocusWithoutLens(Projection),
+    /// The focus distance is not a positive number
+    FocusDistance(f64),
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CameraError::EmptyImage { width, height } => {
+                write!(f, "the image size {width}x{height} has no pixels")
+            }
+            CameraError::AspectRatio(ratio) => {
+                write!(f, "the aspect ratio {ratio} is not a positive number")
+            }
+            CameraError::NoSamples => write!(f, "at least one sample per pixel is needed"),
+            CameraError::FieldOfView { vfov, max } => write!(
+                f,
+                "the vertical field of view {vfov} is not between 0 and {max} degrees"
+            ),
+            CameraError::NoViewDirection => {
+                write!(f, "the camera looks at the point it stands on")
+            }
+            CameraError::UpParallelToView => {
+                write!(f, "the up vector is zero or parallel to the view direction")
+            }
+            CameraError::DefocusAngle(angle) => write!(
+                f,
+                "the defocus angle {angle} is not between 0 and 180 degrees"
+            ),
+            CameraError::DefocusWithoutLens(projection) => write!(
+                f,
+                "the {} projection has no lens, its defocus angle must be 0",
+                projection.name()
+            ),
+            CameraError::FocusDistance(distance) => {
+                write!(f, "the focus distance {distance} is not a positive number")
+            }
+        }
+    }
+}
+
+impl Error for CameraError {}
+
+/// How the height of the image is picked, the last of the two setters wins
+#[derive(Clone, Copy)]
+enum ImageHeight<T> {
+    Pixels(u32),
+    AspectRatio(T),
+}
+
+/// Settings of a [`Camera`], checked by [`CameraBuilder::build`].
+///
+/// Starts as a 100 pixel square image with 10 samples per pixel and 10 bounces, seen
+/// through a pinhole at the origin looking down -Z with a 90 degrees field of view.
+#[derive(Clone, Copy)]
+pub struct CameraBuilder<T> {
+    image_width: u32,
+    image_height: ImageHeight<T>,
+    samples_per_pixel: u32,
+    max_depth: u32,
+    settings: CameraSettings<T>,
+}
+
+impl<T: Float> Default for CameraBuilder<T> {
+    fn default() -> Self {
+        CameraBuilder {
+            image_width: 100,
+            image_height: ImageHeight::AspectRatio(T::one()),
+            samples_per_pixel: 10,
+            max_depth: 10,
+            settings: CameraSettings::default(),
+        }
+    }
+}
+
+impl<T> CameraBuilder<T>
+where
+    T: Float + AsPrimitive<u32>,
+    u32: AsPrimitive<T>,
+{
+    pub fn image_width(mut self, image_width: u32) -> Self {
+        self.image_width = image_width;
+        self
+    }
+
+    pub fn image_height(mut self, image_height: u32) -> Self {
+        self.image_height = ImageHeight::Pixels(image_height);
+        self
+    }
+
+    /// Width over height, the height is rounded to the nearest pixel and
+    /// [`CameraBuilder::build`] fails if that is 0
+    pub fn aspect_ratio(mut self, aspect_ratio: T) -> Self {
+        self.image_height = ImageHeight::AspectRatio(aspect_ratio);
+        self
+    }
+
+    pub fn samples_per_pixel(mut self, samples_per_pixel: u32) -> Self {
+        self.samples_per_pixel = samples_per_pixel;
+        self
+    }
+
+    /// Maximum number of bounces of a ray
+    pub fn max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Vertical field of view
+    pub fn vfov(mut self, vfov: Degrees<T>) -> Self {
+        self.settings.vfov = vfov;
+        self
+    }
+
+    pub fn lookfrom(mut self, lookfrom: Point3<T>) -> Self {
+        self.settings.lookfrom = lookfrom;
+        self
+    }
+
+    pub fn lookat(mut self, lookat: Point3<T>) -> Self {
+        self.settings.lookat = lookat;
+        self
+    }
+
+    /// Direction that is up in the image, it does not need to be perpendicular to the view
+    pub fn up(mut self, up: Vector3<T>) -> Self {
+        self.settings.up = up;
+        self
+    }
+
+    /// Angle of the cone of rays through each pixel, 0 keeps everything in focus. Only
+    /// the perspective projection has a lens, [`CameraBuilder::build`] fails for the
+    /// others unless this is 0.
+    pub fn defocus_angle(mut self, defocus_angle: Degrees<T>) -> Self {
+        self.settings.defocus_angle = defocus_angle;
+        self
+    }
+
+    /// Distance from `lookfrom` to the plane in perfect focus
+    pub fn focus_dist(mut self, focus_dist: T) -> Self {
+        self.settings.focus_dist = focus_dist;
+        self
+    }
+
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.settings.projection = projection;
+        self
+    }
+
+    pub fn build(&self) -> Result<Camera<T>, CameraError> {
+        let f64_of = |x: T| x.to_f64().unwrap_or(f64::NAN);
+        let CameraSettings {
+            lookfrom,
+            lookat,
+            up,
+            vfov,
+            defocus_angle,
+            focus_dist,
+            projection,
+        } = self.settings;
+
+        let image_width = self.image_width;
+        let image_height = match self.image_height {
+            ImageHeight::Pixels(image_height) => image_height,
+            ImageHeight::AspectRatio(aspect_ratio) => {
+                if !(aspect_ratio > T::zero() && aspect_ratio.is_finite()) {
+                    return Err(CameraError::AspectRatio(f64_of(aspect_ratio)));
+                }
+                // Rounded so that an aspect ratio of width / height gives back the same
+                // height, a ratio wider than twice the width leaves no rows
+                (image_width.as_() / aspect_ratio).round().as_()
+            }
+        };
+        if image_width == 0 || image_height == 0 {
+            return Err(CameraError::EmptyImage {
+                width: image_width,
+                height: image_height,
+            });
+        }
+        if self.samples_per_pixel == 0 {
+            return Err(CameraError::NoSamples);
+        }
+        let vfov_valid = match projection {
+            Projection::Perspective | Projection::Orthographic => {
+                vfov.0 > T::zero() && vfov.0 < T::constant(180.0)
+            }
+            Projection::Fisheye => vfov.0 > T::zero() && vfov.0 <= T::constant(360.0),
+            Projection::Equirectangular => true,
+        };
+        if !vfov_valid {
+            let max = match projection {
+                Projection::Fisheye => 360.0,
+                _ => 180.0,
+            };
+            return Err(CameraError::FieldOfView {
+                vfov: f64_of(vfov.0),
+                max,
+            });
+        }
+        if !(defocus_angle.0 >= T::zero() && defocus_angle.0 < T::constant(180.0)) {
+            return Err(CameraError::DefocusAngle(f64_of(defocus_angle.0)));
+        }
+        if defocus_angle.0 > T::zero() && projection != Projection::Perspective {
+            return Err(CameraError::DefocusWithoutLens(projection));
+        }
+        if !(focus_dist > T::zero() && focus_dist.is_finite()) {
+            return Err(CameraError::FocusDistance(f64_of(focus_dist)));
+        }
+        let view = lookfrom - lookat;
+        if !view.length_squared().is_normal() {
+            return Err(CameraError::NoViewDirection);
+        }
+
+        // Determine viewport dimensions, only the perspective and orthographic
+        // projections have one
+        let theta = vfov.0.to_radians().min(T::constant(179.0).to_radians());
         let h = (theta * T::constant(0.5)).tan();
         let viewport_height = T::constant(2.0) * h * focus_dist;
         let viewport_width = viewport_height * (image_width.as_() / image_height.as_());
 
-        // Calculate the u,v,w unit basis vectors for the camera coordinate frame
-        let w = (lookfrom - lookat).unit_vector();
-        let u = up.cross(w).unit_vector();
+        // Calculate the u,v,w unit basis vectors for the camera coordinate frame. The
+        // cross product vanishes when up is along the view direction.
+        let w = view.unit_vector();
+        let side = up.cross(w);
+        let side_squared = side.length_squared();
+        if side_squared.is_nan() || side_squared <= up.length_squared() * T::constant(1e-12) {
+            return Err(CameraError::UpParallelToView);
+        }
+        let u = side.unit_vector();
         let v = w.cross(u);
 
         // Calculate the vectors across the horizontal and down the vertical viewport edges
@@ -72,18 +386,51 @@ where
         let defocus_disc_u = u * defocus_radius;
         let defocus_disc_v = v * defocus_radius;
 
-        Camera {
+        Ok(Camera {
             center: lookfrom,
             pixel00_loc,
             pixel_delta_u,
             pixel_delta_v,
             defocus_disc_u,
             defocus_disc_v,
+            projection,
+            basis: [u, v, w],
+            vfov: vfov.0.to_radians(),
+            focus_dist,
             image_width,
             image_height,
-            samples_per_pixel,
-            max_depth,
-        }
+            samples_per_pixel: self.samples_per_pixel,
+            max_depth: self.max_depth,
+        })
+    }
+}
+
+pub struct Camera<T> {
+    center: Point3<T>,
+    pixel00_loc: Point3<T>,
+    pixel_delta_u: Vector3<T>,
+    pixel_delta_v: Vector3<T>,
+    defocus_disc_u: Vector3<T>,
+    defocus_disc_v: Vector3<T>,
+    projection: Projection,
+    /// right, up and backwards
+    basis: [Vector3<T>; 3],
+    /// in radians
+    vfov: T,
+    focus_dist: T,
+    image_width: u32,
+    image_height: u32,
+    samples_per_pixel: u32,
+    max_depth: u32,
+}
+
+impl<T> Camera<T>
+where
+    T: Float + AsPrimitive<u32>,
+    u32: AsPrimitive<T>,
+{
+    pub fn builder() -> CameraBuilder<T> {
+        CameraBuilder::default()
     }
 
     pub fn get_image_width(&self) -> u32 {
@@ -93,29 +440,108 @@ where
         self.image_height
     }
 
-    pub fn render<U, World>(&self, world: &World, rng: &mut RandomNumberGenerator) -> Vec<RGB<U>>
+    /// Renders the image on `thread_count` threads, or one per available core if it is 0.
+    ///
+    /// Every sample of every pixel draws its random numbers from its own stream derived from
+    /// `seed`, so the result does not depend on how many threads are used, the order tiles
+    /// finish in or which part of the image is rendered.
+    pub fn render<U, World, Env>(
+        &self,
+        world: &World,
+        background: &Env,
+        seed: u32,
+        thread_count: usize,
+    ) -> Vec<RGB<U>>
     where
         T: AsPrimitive<U>,
         u32: AsPrimitive<U>,
         U: 'static + Float,
         World: Hittable<T, U>,
+        Env: Background<T, U> + ?Sized,
         for<'a> RNGAdapter<'a>: RandomSource<T>,
     {
-        let mut data = Vec::with_capacity((self.image_width * self.image_height * 3) as usize);
-        let mut rng = RNGAdapter(rng);
-
-        for j in 0..self.image_height {
-            eprint!(
-                "\rScanlines remaining {}/{} ",
-                self.image_height - j,
-                self.image_height
-            );
-            for i in 0..self.image_width {
-                let mut rgb = RGB::white();
-                for _ in 0..self.samples_per_pixel {
-                    let r = self.get_ray(i, j, &mut rng);
+        let tiles_x = self.image_width.div_ceil(TILE_SIZE);
+        let tiles_y = self.image_height.div_ceil(TILE_SIZE);
+        let tile_count = tiles_x * tiles_y;
+
+        let thread_count = match thread_count {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        let thread_count = thread_count.min(tile_count as usize);
+
+        let next_tile = AtomicU32::new(0);
+        let rendered_tiles: Vec<(u32, Vec<RGB<U>>)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..thread_count)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut rendered = Vec::new();
+                        loop {
+                            let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+                            if tile >= tile_count {
+                                break;
+                            }
+                            eprint!("\rTiles remaining {}/{} ", tile_count - tile, tile_count);
+                            let x0 = (tile % tiles_x) * TILE_SIZE;
+                            let y0 = (tile / tiles_x) * TILE_SIZE;
+                            rendered
+                                .push((tile, self.render_tile(world, background, seed, x0, y0)));
+                        }
+                        rendered
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        let mut data = vec![RGB::black(); self.image_width as usize * self.image_height as usize];
+        for (tile, pixels) in rendered_tiles {
+            let x0 = (tile % tiles_x) * TILE_SIZE;
+            let y0 = (tile / tiles_x) * TILE_SIZE;
+            let tile_width = TILE_SIZE.min(self.image_width - x0) as usize;
+            for (row, line) in pixels.chunks(tile_width).enumerate() {
+                let start = ((y0 as usize + row) * self.image_width as usize) + x0 as usize;
+                data[start..start + tile_width].copy_from_slice(line);
+            }
+        }
+        data
+    }
+
+    /// Renders the tile with its upper left corner at pixel (x0, y0), clipped to the image.
+    /// Pixels are returned row by row.
+    fn render_tile<U, World, Env>(
+        &self,
+        world: &World,
+        background: &Env,
+        seed: u32,
+        x0: u32,
+        y0: u32,
+    ) -> Vec<RGB<U>>
+    where
+        T: AsPrimitive<U>,
+        u32: AsPrimitive<U>,
+        U: 'static + Float,
+        World: Hittable<T, U>,
+        Env: Background<T, U> + ?Sized,
+        for<'a> RNGAdapter<'a>: RandomSource<T>,
+    {
+        let x1 = (x0 + TILE_SIZE).min(self.image_width);
+        let y1 = (y0 + TILE_SIZE).min(self.image_height);
+
+        let mut data = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
 
-                    rgb += Camera::ray_color(&r, self.max_depth, world, &mut rng);
+        for j in y0..y1 {
+            for i in x0..x1 {
+                let mut rgb = RGB::black();
+                for sample in 0..self.samples_per_pixel {
+                    let mut rng = RandomNumberGenerator::for_pixel_sample(seed, i, j, sample);
+                    let mut rng = RNGAdapter(&mut rng);
+                    if let Some(r) = self.get_ray(i, j, &mut rng) {
+                        rgb += Camera::ray_color(&r, self.max_depth, world, background, &mut rng);
+                    }
                 }
                 data.push(rgb / self.samples_per_pixel.as_());
             }
@@ -123,30 +549,40 @@ where
         data
     }
 
-    fn ray_color<U, World, RNG>(ray: &Ray<T>, depth: u32, world: &World, rng: &mut RNG) -> RGB<U>
+    fn ray_color<U, World, Env, RNG>(
+        ray: &Ray<T>,
+        depth: u32,
+        world: &World,
+        background: &Env,
+        rng: &mut RNG,
+    ) -> RGB<U>
     where
         T: AsPrimitive<U>,
         U: 'static + Float,
         World: Hittable<T, U>,
+        Env: Background<T, U> + ?Sized,
         RNG: RandomSource<T>,
     {
         assert!(ray.direction().is_unit_vector());
-        if depth <= 0 {
+        if depth == 0 {
             return RGB::black();
         }
         match world.hit(ray, Interval::new(T::constant(0.001), T::infinity())) {
-            Some(hit_record) => match hit_record.material.scatter(rng, ray, &hit_record) {
-                Some((scattered_ray, attenuation)) => {
-                    attenuation * Self::ray_color(&scattered_ray, depth - 1, world, rng)
+            Some(hit_record) => {
+                let emitted =
+                    hit_record
+                        .material
+                        .emitted(hit_record.u, hit_record.v, hit_record.point);
+                match hit_record.material.scatter(rng, ray, &hit_record) {
+                    Some((scattered_ray, attenuation)) => {
+                        emitted
+                            + attenuation
+                                * Self::ray_color(&scattered_ray, depth - 1, world, background, rng)
+                    }
+                    None => emitted,
                 }
-                None => RGB::black(),
-            },
-            None => {
-                let unit_direction = Vector3::unit_vector(ray.direction());
-                let a = ((unit_direction.y + T::constant(1.0)) * T::constant(0.5)).as_();
-                RGB::white() * (U::constant(1.0) - a)
-                    + RGB::new(U::constant(0.5), U::constant(0.7), U::constant(1.0)) * a
             }
+            None => background.color(ray.direction()),
         }
     }
 
@@ -154,21 +590,76 @@ where
         // Returns a random point in the square surrounding a pixel at the origin.
         let px = T::constant(-0.5) + rng.next();
         let py = T::constant(-0.5) + rng.next();
-        return (self.pixel_delta_u * px) + (self.pixel_delta_v * py);
+        (self.pixel_delta_u * px) + (self.pixel_delta_v * py)
     }
 
-    fn get_ray<RNG: RandomSource<T>>(&self, i: u32, j: u32, rng: &mut RNG) -> Ray<T> {
-        // Get a randomly-sampled camera ray for the pixel at location i,j, originating from
-        // the camera defocus disk.
+    /// Randomly-sampled camera ray through the pixel at location i,j, none for the
+    /// pixels of a fisheye image outside its field of view
+    fn get_ray<RNG: RandomSource<T>>(&self, i: u32, j: u32, rng: &mut RNG) -> Option<Ray<T>> {
+        match self.projection {
+            Projection::Perspective => {
+                // originates from the camera defocus disk
+                let pixel_sample = self.pixel_sample(i, j, rng);
+                let ray_origin = self.defocus_disc_sample(rng);
+                let ray_direction = (pixel_sample - ray_origin).unit_vector();
+                Some(Ray::new(ray_origin, ray_direction))
+            }
+            Projection::Orthographic => {
+                // from the plane of the camera straight towards the focus plane
+                let [_, _, w] = self.basis;
+                let pixel_sample = self.pixel_sample(i, j, rng);
+                Some(Ray::new(pixel_sample + w * self.focus_dist, -w))
+            }
+            Projection::Fisheye => {
+                // offset from the center of the image in half image heights, y up
+                let (x, y) = self.image_sample(i, j, rng);
+                let half_height = T::constant(0.5) * self.image_height.as_();
+                let x = (x - T::constant(0.5) * self.image_width.as_()) / half_height;
+                let y = (half_height - y) / half_height;
 
+                // the field of view ends at a radius of 1, the top and bottom edges
+                let radius = (x * x + y * y).sqrt();
+                if radius > T::one() {
+                    return None;
+                }
+                let theta = radius * self.vfov * T::constant(0.5);
+                let (sin_theta, cos_theta) = theta.sin_cos();
+                let (sin_phi, cos_phi) = if radius > T::zero() {
+                    (y / radius, x / radius)
+                } else {
+                    (T::zero(), T::one())
+                };
+                let [u, v, w] = self.basis;
+                let direction =
+                    u * (sin_theta * cos_phi) + v * (sin_theta * sin_phi) - w * cos_theta;
+                Some(Ray::new(self.center, direction.unit_vector()))
+            }
+            Projection::Equirectangular => {
+                let (x, y) = self.image_sample(i, j, rng);
+                let longitude = (x / self.image_width.as_() - T::constant(0.5)) * T::TAU();
+                let latitude = (T::constant(0.5) - y / self.image_height.as_()) * T::PI();
+
+                let (sin_longitude, cos_longitude) = longitude.sin_cos();
+                let (sin_latitude, cos_latitude) = latitude.sin_cos();
+                let [u, v, w] = self.basis;
+                let direction = u * (cos_latitude * sin_longitude) + v * sin_latitude
+                    - w * (cos_latitude * cos_longitude);
+                Some(Ray::new(self.center, direction.unit_vector()))
+            }
+        }
+    }
+
+    /// Random point in the pixel at location i,j on the focus plane
+    fn pixel_sample<RNG: RandomSource<T>>(&self, i: u32, j: u32, rng: &mut RNG) -> Point3<T> {
         let pixel_center =
             self.pixel00_loc + (self.pixel_delta_u * i.as_()) + (self.pixel_delta_v * j.as_());
-        let pixel_sample = pixel_center + self.pixel_sample_square(rng);
-
-        let ray_origin = self.defocus_disc_sample(rng);
-        let ray_direction = (pixel_sample - ray_origin).unit_vector();
+        pixel_center + self.pixel_sample_square(rng)
+    }
 
-        Ray::new(ray_origin, ray_direction)
+    /// Random point in the pixel at location i,j in pixel coordinates, from the upper
+    /// left corner of the image
+    fn image_sample<RNG: RandomSource<T>>(&self, i: u32, j: u32, rng: &mut RNG) -> (T, T) {
+        (i.as_() + rng.next(), j.as_() + rng.next())
     }
 
     fn defocus_disc_sample<RNG: RandomSource<T>>(&self, rng: &mut RNG) -> Point3<T> {
@@ -177,3 +668,371 @@ where
         self.center + self.defocus_disc_u * p.x + self.defocus_disc_v * p.y
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(builder: CameraBuilder<f64>) -> CameraError {
+        match builder.build() {
+            Ok(_) => panic!("expected an error"),
+            Err(error) => error,
+        }
+    }
+
+    fn vector_eq(a: Vector3<f64>, b: Vector3<f64>) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn empty_images() {
+        assert_eq!(
+            error(Camera::builder().image_width(0)),
+            CameraError::EmptyImage {
+                width: 0,
+                height: 0
+            }
+        );
+        assert_eq!(
+            error(Camera::builder().image_width(10).image_height(0)),
+            CameraError::EmptyImage {
+                width: 10,
+                height: 0
+            }
+        );
+        // 1 / 3 rounds to no rows at all
+        assert_eq!(
+            error(Camera::builder().image_width(1).aspect_ratio(3.0)),
+            CameraError::EmptyImage {
+                width: 1,
+                height: 0
+            }
+        );
+        let camera = Camera::builder()
+            .image_width(1)
+            .aspect_ratio(1.9)
+            .build()
+            .unwrap();
+        assert_eq!(camera.get_image_height(), 1);
+    }
+
+    #[test]
+    fn aspect_ratios() {
+        for ratio in [f64::NAN, -1.0, 0.0, f64::INFINITY] {
+            match error(Camera::builder().aspect_ratio(ratio)) {
+                CameraError::AspectRatio(r) => assert!(r.is_nan() || r == ratio),
+                other => panic!("{ratio} gave {other:?}"),
+            }
+        }
+        // the last of the height setters wins
+        let camera = Camera::builder()
+            .image_width(400)
+            .aspect_ratio(f64::NAN)
+            .image_height(300)
+            .build()
+            .unwrap();
+        assert_eq!(camera.get_image_height(), 300);
+        let camera = Camera::builder()
+            .image_width(400)
+            .image_height(300)
+            .aspect_ratio(16.0 / 9.0)
+            .build()
+            .unwrap();
+        assert_eq!(camera.get_image_height(), 225);
+    }
+
+    #[test]
+    fn no_samples() {
+        assert_eq!(
+            error(Camera::builder().samples_per_pixel(0)),
+            CameraError::NoSamples
+        );
+    }
+
+    #[test]
+    fn fields_of_view() {
+        for vfov in [0.0, -10.0, 180.0, 200.0, f64::NAN] {
+            match error(Camera::builder().vfov(Degrees(vfov))) {
+                CameraError::FieldOfView { max, .. } => assert_eq!(max, 180.0),
+                other => panic!("{vfov} gave {other:?}"),
+            }
+        }
+        assert!(Camera::builder().vfov(Degrees(179.9)).build().is_ok());
+
+        let fisheye = Camera::builder().projection(Projection::Fisheye);
+        assert!(fisheye.vfov(Degrees(180.0)).build().is_ok());
+        assert!(fisheye.vfov(Degrees(360.0)).build().is_ok());
+        assert_eq!(
+            error(fisheye.vfov(Degrees(361.0))),
+            CameraError::FieldOfView {
+                vfov: 361.0,
+                max: 360.0
+            }
+        );
+        assert!(matches!(
+            error(fisheye.vfov(Degrees(0.0))),
+            CameraError::FieldOfView { .. }
+        ));
+    }
+
+    #[test]
+    fn no_view_direction() {
+        let point = Point3::new(1.0, 2.0, 3.0);
+        assert_eq!(
+            error(Camera::builder().lookfrom(point).lookat(point)),
+            CameraError::NoViewDirection
+        );
+    }
+
+    #[test]
+    fn up_parallel_to_view() {
+        // the default camera looks down -Z
+        for up in [
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(0.0, 0.0, -1.0),
+            Vector3::new(0.0, 0.0, 1000.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(f64::NAN, 0.0, 0.0),
+        ] {
+            assert_eq!(
+                error(Camera::builder().up(up)),
+                CameraError::UpParallelToView,
+                "up ({}, {}, {})",
+                up.x,
+                up.y,
+                up.z
+            );
+        }
+        // only the component across the view matters
+        assert!(Camera::builder()
+            .up(Vector3::new(0.0, 0.001, 1.0))
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn defocus_angles() {
+        for angle in [-1.0, 180.0, f64::NAN] {
+            match error(Camera::builder().defocus_angle(Degrees(angle))) {
+                CameraError::DefocusAngle(a) => assert!(a.is_nan() || a == angle),
+                other => panic!("{angle} gave {other:?}"),
+            }
+        }
+        assert!(Camera::builder()
+            .defocus_angle(Degrees(10.0))
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn focus_distances() {
+        for distance in [0.0, -1.0, f64::INFINITY, f64::NAN] {
+            match error(Camera::builder().focus_dist(distance)) {
+                CameraError::FocusDistance(d) => assert!(d.is_nan() || d == distance),
+                other => panic!("{distance} gave {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn matches_the_old_constructor() {
+        let (aspect_ratio, image_width) = (16.0 / 9.0, 400u32);
+        let vfov = 20.0f64;
+        let lookfrom = Point3::new(13.0, 2.0, 3.0);
+        let lookat = Point3::new(0.0, 0.0, 0.0);
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let (defocus_angle, focus_dist) = (0.6f64, 10.0);
+
+        let camera = Camera::builder()
+            .aspect_ratio(aspect_ratio)
+            .image_width(image_width)
+            .samples_per_pixel(500)
+            .max_depth(50)
+            .vfov(Degrees(vfov))
+            .lookfrom(lookfrom)
+            .lookat(lookat)
+            .up(up)
+            .defocus_angle(Degrees(defocus_angle))
+            .focus_dist(focus_dist)
+            .build()
+            .unwrap();
+
+        // the geometry Camera::new used to compute
+        let image_height = (image_width as f64 / aspect_ratio) as u32;
+        let h = (vfov.to_radians() / 2.0).tan();
+        let viewport_height = 2.0 * h * focus_dist;
+        let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
+        let w = (lookfrom - lookat).unit_vector();
+        let u = up.cross(w).unit_vector();
+        let v = w.cross(u);
+        let viewport_u = u * viewport_width;
+        let viewport_v = v * -viewport_height;
+        let pixel_delta_u = viewport_u / image_width as f64;
+        let pixel_delta_v = viewport_v / image_height as f64;
+        let upper_left = lookfrom - w * focus_dist - viewport_u / 2.0 - viewport_v / 2.0;
+        let pixel00_loc = upper_left + (pixel_delta_u + pixel_delta_v) * 0.5;
+        let defocus_radius = focus_dist * (defocus_angle / 2.0).to_radians().tan();
+
+        assert_eq!(camera.get_image_width(), 400);
+        assert_eq!(camera.get_image_height(), image_height);
+        assert_eq!(camera.samples_per_pixel, 500);
+        assert_eq!(camera.max_depth, 50);
+        assert!(vector_eq(
+            camera.center - lookfrom,
+            Vector3::new(0.0, 0.0, 0.0)
+        ));
+        assert!(vector_eq(
+            camera.pixel00_loc - pixel00_loc,
+            Vector3::new(0.0, 0.0, 0.0)
+        ));
+        assert!(vector_eq(camera.pixel_delta_u, pixel_delta_u));
+        assert!(vector_eq(camera.pixel_delta_v, pixel_delta_v));
+        assert!(vector_eq(camera.defocus_disc_u, u * defocus_radius));
+        assert!(vector_eq(camera.defocus_disc_v, v * defocus_radius));
+    }
+
+    /// Returns the same number every time, 0.5 puts samples in the middle of their pixel
+    struct Fixed(f64);
+
+    impl RandomSource<f64> for Fixed {
+        fn next(&mut self) -> f64 {
+            self.0
+        }
+        fn next_range(&mut self, min: f64, max: f64) -> f64 {
+            min + self.0 * (max - min)
+        }
+        fn next_bool_with_probability(&mut self, p: f64) -> bool {
+            self.0 < p
+        }
+    }
+
+    fn looking_from_the_side(projection: Projection) -> CameraBuilder<f64> {
+        Camera::builder()
+            .lookfrom(Point3::new(1.0, 2.0, 3.0))
+            .lookat(Point3::new(4.0, -1.0, 0.0))
+            .up(Vector3::new(0.0, 1.0, 0.0))
+            .projection(projection)
+    }
+
+    #[test]
+    fn center_pixel_looks_along_the_view() {
+        let view = Vector3::new(3.0, -3.0, -3.0).unit_vector();
+        for projection in Projection::NAMES.map(|name| Projection::from_name(name).unwrap()) {
+            let (width, height) = match projection {
+                Projection::Equirectangular => (201, 101),
+                _ => (101, 101),
+            };
+            let camera = looking_from_the_side(projection)
+                .image_width(width)
+                .image_height(height)
+                .build()
+                .unwrap();
+            let [_, _, w] = camera.basis;
+            assert!(vector_eq(w, -view));
+            let ray = camera
+                .get_ray(width / 2, height / 2, &mut Fixed(0.5))
+                .unwrap();
+            assert!(vector_eq(ray.direction(), view), "{}", projection.name());
+            assert!(vector_eq(
+                ray.origin() - Point3::new(1.0, 2.0, 3.0),
+                Vector3::new(0.0, 0.0, 0.0)
+            ));
+        }
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let camera = looking_from_the_side(Projection::Orthographic)
+            .image_width(40)
+            .image_height(30)
+            .build()
+            .unwrap();
+        let [_, _, w] = camera.basis;
+        let center = Point3::new(1.0, 2.0, 3.0);
+        let mut origins = Vec::new();
+        for (i, j) in [(0, 0), (39, 0), (0, 29), (39, 29), (17, 5)] {
+            for sample in [0.0, 0.5, 0.99] {
+                let ray = camera.get_ray(i, j, &mut Fixed(sample)).unwrap();
+                assert!(vector_eq(ray.direction(), -w));
+                // every ray starts on the plane of the camera
+                assert!((ray.origin() - center).dot(w).abs() < 1e-9);
+                origins.push(ray.origin());
+            }
+        }
+        let spread = origins[0] - origins[3];
+        assert!(spread.length() > 1.0);
+    }
+
+    #[test]
+    fn fisheye_is_black_outside_its_field_of_view() {
+        let camera = looking_from_the_side(Projection::Fisheye)
+            .vfov(Degrees(360.0))
+            .image_width(101)
+            .image_height(101)
+            .build()
+            .unwrap();
+        let mut rng = Fixed(0.5);
+        // the corners are further than half the height from the center
+        for (i, j) in [(0, 0), (100, 0), (0, 100), (100, 100)] {
+            assert!(camera.get_ray(i, j, &mut rng).is_none());
+        }
+        // the middle of the top edge is almost straight behind
+        let [_, _, w] = camera.basis;
+        let ray = camera.get_ray(50, 0, &mut rng).unwrap();
+        assert!(ray.direction().dot(w) > 0.99);
+
+        // at 180 degrees the top edge looks up
+        let camera = looking_from_the_side(Projection::Fisheye)
+            .vfov(Degrees(180.0))
+            .image_width(101)
+            .image_height(101)
+            .build()
+            .unwrap();
+        let [_, v, _] = camera.basis;
+        let ray = camera.get_ray(50, 0, &mut rng).unwrap();
+        assert!(ray.direction().dot(v) > 0.999);
+        assert!(camera.get_ray(0, 0, &mut rng).is_none());
+    }
+
+    #[test]
+    fn equirectangular_poles_are_up_and_down() {
+        let camera = looking_from_the_side(Projection::Equirectangular)
+            .image_width(200)
+            .image_height(100)
+            .build()
+            .unwrap();
+        let [_, v, w] = camera.basis;
+        for i in [0, 50, 199] {
+            // the top edge of the first row and the bottom edge of the last one
+            let top = camera.get_ray(i, 0, &mut Fixed(0.0)).unwrap();
+            assert!(vector_eq(top.direction(), v));
+            let bottom = camera.get_ray(i, 99, &mut Fixed(1.0)).unwrap();
+            assert!(vector_eq(bottom.direction(), -v));
+        }
+        // the left and right edges of the horizon look behind the camera
+        let left = camera.get_ray(0, 50, &mut Fixed(0.0)).unwrap();
+        assert!(vector_eq(left.direction(), w));
+        let right = camera.get_ray(199, 49, &mut Fixed(1.0)).unwrap();
+        assert!(vector_eq(right.direction(), w));
+    }
+
+    #[test]
+    fn only_perspective_has_a_lens() {
+        assert!(looking_from_the_side(Projection::Perspective)
+            .defocus_angle(Degrees(0.6))
+            .build()
+            .is_ok());
+        for projection in [
+            Projection::Orthographic,
+            Projection::Fisheye,
+            Projection::Equirectangular,
+        ] {
+            let builder = looking_from_the_side(projection);
+            assert!(builder.build().is_ok());
+            assert_eq!(
+                error(builder.defocus_angle(Degrees(0.6))),
+                CameraError::DefocusWithoutLens(projection)
+            );
+        }
+    }
+}
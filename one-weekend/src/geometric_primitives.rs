@@ -1,21 +1,26 @@
+use std::sync::Arc;
+
+use math::Aabb;
 use math::Float;
 use math::Interval;
 use math::Point3;
 use math::Ray;
+use math::Vector3;
 
 use crate::HitRecord;
 use crate::Hittable;
 use crate::Material;
+use crate::TriangleMesh;
 
-#[derive(Clone, Copy)]
-pub struct Sphere<'a, T, U> {
+#[derive(Clone)]
+pub struct Sphere<T, U> {
     center: Point3<T>,
     radius: T,
-    material: &'a dyn Material<T, U>,
+    material: Arc<dyn Material<T, U>>,
 }
 
-impl<'a, T, U> Sphere<'a, T, U> {
-    pub fn new(center: Point3<T>, radius: T, material: &'a dyn Material<T, U>) -> Sphere<'a, T, U> {
+impl<T, U> Sphere<T, U> {
+    pub fn new(center: Point3<T>, radius: T, material: Arc<dyn Material<T, U>>) -> Sphere<T, U> {
         Sphere {
             center,
             radius,
@@ -24,12 +29,27 @@ impl<'a, T, U> Sphere<'a, T, U> {
     }
 }
 
-impl<'a, T, U> Hittable<T, U> for Sphere<'a, T, U>
+impl<T, U> Sphere<T, U>
+where
+    T: Float,
+{
+    /// Maps a point on the unit sphere to (u, v) in [0,1]x[0,1].
+    /// u is the angle around the Y axis starting at X=-1, v the angle from Y=-1 to Y=+1
+    /// y is clamped first, a normalized pole can be slightly outside [-1,1] and acos
+    /// would give NaN
+    fn sphere_uv(p: Vector3<T>) -> (T, T) {
+        let theta = (-p.y).max(-T::one()).min(T::one()).acos();
+        let phi = (-p.z).atan2(p.x) + T::PI();
+        (phi / (T::constant(2.0) * T::PI()), theta / T::PI())
+    }
+}
+
+impl<T, U> Hittable<T, U> for Sphere<T, U>
 where
     T: Float,
     U: Float,
 {
-    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<'a, T, U>> {
+    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<'_, T, U>> {
         let oc = ray.origin() - self.center;
         let a = ray.direction().length_squared();
         let half_b = oc.dot(ray.direction());
@@ -49,40 +69,268 @@ where
         }
 
         let point = ray.at(root);
+        let outward_normal = (point - self.center) / self.radius;
+        let (u, v) = Self::sphere_uv(outward_normal);
 
         Some(HitRecord::new(
-            &ray,
+            ray,
             point,
-            (point - self.center) / self.radius,
-            self.material,
+            outward_normal,
+            self.material.as_ref(),
             root,
+            u,
+            v,
         ))
     }
+
+    fn bounding_box(&self) -> Aabb<T> {
+        let radius = self.radius.abs();
+        let rvec = Vector3::new(radius, radius, radius);
+        Aabb::from_points(self.center - rvec, self.center + rvec)
+    }
+}
+
+/// Parallelogram spanned by two edge vectors from a corner
+#[derive(Clone)]
+pub struct Quad<T, U> {
+    corner: Point3<T>,
+    u: Vector3<T>,
+    v: Vector3<T>,
+    material: Arc<dyn Material<T, U>>,
+    normal: Vector3<T>,
+    // plane equation, dot(normal, p) = d
+    d: T,
+    // n / dot(n, n), projects hit points onto the (u, v) frame
+    w: Vector3<T>,
+}
+
+impl<T, U> Quad<T, U>
+where
+    T: Float,
+{
+    pub fn new(
+        corner: Point3<T>,
+        u: Vector3<T>,
+        v: Vector3<T>,
+        material: Arc<dyn Material<T, U>>,
+    ) -> Quad<T, U> {
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+        Quad {
+            corner,
+            u,
+            v,
+            material,
+            normal,
+            d: normal.dot(corner.into()),
+            w: n / n.dot(n),
+        }
+    }
+}
+
+impl<T, U> Hittable<T, U> for Quad<T, U>
+where
+    T: Float,
+    U: Float,
+{
+    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<'_, T, U>> {
+        let denom = self.normal.dot(ray.direction());
+
+        // No hit if the ray is parallel to the plane
+        if denom.abs() < T::constant(1e-8) {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(ray.origin().into())) / denom;
+        if !ray_t.contains(t) {
+            return None;
+        }
+
+        // Determine if the hit point lies within the planar shape using its plane coordinates
+        let point = ray.at(t);
+        let planar_hitpt_vector = point - self.corner;
+        let alpha = self.w.dot(planar_hitpt_vector.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar_hitpt_vector));
+
+        let unit = Interval::new(T::constant(0.0), T::constant(1.0));
+        if !unit.contains(alpha) || !unit.contains(beta) {
+            return None;
+        }
+
+        Some(HitRecord::new(
+            ray,
+            point,
+            self.normal,
+            self.material.as_ref(),
+            t,
+            alpha,
+            beta,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb<T> {
+        let diagonal1 = Aabb::from_points(self.corner, self.corner + self.u + self.v);
+        let diagonal2 = Aabb::from_points(self.corner + self.u, self.corner + self.v);
+        diagonal1
+            .union(&diagonal2)
+            .pad_to_minimums(T::constant(0.0001))
+    }
+}
+
+/// Corner and edges of the six faces of the box between two opposite corners, with the
+/// normals pointing out
+pub(crate) fn box_sides<T: Float>(
+    a: Point3<T>,
+    b: Point3<T>,
+) -> [(Point3<T>, Vector3<T>, Vector3<T>); 6] {
+    let min = Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+    let max = Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+    let zero = T::constant(0.0);
+    let dx = Vector3::new(max.x - min.x, zero, zero);
+    let dy = Vector3::new(zero, max.y - min.y, zero);
+    let dz = Vector3::new(zero, zero, max.z - min.z);
+
+    [
+        (Point3::new(min.x, min.y, max.z), dx, dy),  // front
+        (Point3::new(max.x, min.y, max.z), -dz, dy), // right
+        (Point3::new(max.x, min.y, min.z), -dx, dy), // back
+        (Point3::new(min.x, min.y, min.z), dz, dy),  // left
+        (Point3::new(min.x, max.y, max.z), dx, -dz), // top
+        (Point3::new(min.x, min.y, min.z), dx, dz),  // bottom
+    ]
 }
 
-pub enum GeometricPrimitive<'a, T, U> {
-    Sphere(Sphere<'a, T, U>),
+/// Single triangle, its surface coordinates are the barycentric coordinates of the hit
+#[derive(Clone)]
+pub struct Triangle<T, U> {
+    vertices: [Point3<T>; 3],
+    material: Arc<dyn Material<T, U>>,
+}
+
+impl<T, U> Triangle<T, U> {
+    pub fn new(
+        p0: Point3<T>,
+        p1: Point3<T>,
+        p2: Point3<T>,
+        material: Arc<dyn Material<T, U>>,
+    ) -> Triangle<T, U> {
+        Triangle {
+            vertices: [p0, p1, p2],
+            material,
+        }
+    }
+}
+
+/// Möller–Trumbore ray/triangle intersection.
+/// Returns the ray distance and the barycentric coordinates of p1 and p2.
+pub(crate) fn intersect_triangle<T: Float>(
+    ray: &Ray<T>,
+    ray_t: &Interval<T>,
+    [p0, p1, p2]: &[Point3<T>; 3],
+) -> Option<(T, T, T)> {
+    let edge1 = *p1 - *p0;
+    let edge2 = *p2 - *p0;
+    let pvec = ray.direction().cross(edge2);
+    let det = edge1.dot(pvec);
+
+    // No hit if the ray is parallel to the triangle
+    if det.abs() < T::min_positive_value() {
+        return None;
+    }
+    let inv_det = T::one() / det;
+
+    let tvec = ray.origin() - *p0;
+    let b1 = tvec.dot(pvec) * inv_det;
+    if b1 < T::constant(0.0) || b1 > T::one() {
+        return None;
+    }
+
+    let qvec = tvec.cross(edge1);
+    let b2 = ray.direction().dot(qvec) * inv_det;
+    if b2 < T::constant(0.0) || b1 + b2 > T::one() {
+        return None;
+    }
+
+    let t = edge2.dot(qvec) * inv_det;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+/// Counter-clockwise winding is the front side
+pub(crate) fn triangle_normal<T: Float>([p0, p1, p2]: &[Point3<T>; 3]) -> Vector3<T> {
+    (*p1 - *p0).cross(*p2 - *p0).unit_vector()
+}
+
+impl<T, U> Hittable<T, U> for Triangle<T, U>
+where
+    T: Float,
+    U: Float,
+{
+    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<'_, T, U>> {
+        let (t, b1, b2) = intersect_triangle(ray, &ray_t, &self.vertices)?;
+        Some(HitRecord::new(
+            ray,
+            ray.at(t),
+            triangle_normal(&self.vertices),
+            self.material.as_ref(),
+            t,
+            b1,
+            b2,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb<T> {
+        Aabb::surrounding_points(self.vertices).pad_to_minimums(T::constant(0.0001))
+    }
+}
+
+pub enum GeometricPrimitive<T, U> {
+    Sphere(Sphere<T, U>),
+    Quad(Quad<T, U>),
+    Triangle(Triangle<T, U>),
+    Mesh(TriangleMesh<T, U>),
     Other(Box<dyn Hittable<T, U>>),
 }
 
-impl<'a, T, U> Hittable<T, U> for GeometricPrimitive<'a, T, U>
+impl<T, U> Hittable<T, U> for GeometricPrimitive<T, U>
 where
-    Sphere<'a, T, U>: Hittable<T, U>,
+    Sphere<T, U>: Hittable<T, U>,
+    Quad<T, U>: Hittable<T, U>,
+    Triangle<T, U>: Hittable<T, U>,
+    TriangleMesh<T, U>: Hittable<T, U>,
 {
-    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<T, U>> {
+    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<'_, T, U>> {
         match self {
             GeometricPrimitive::Sphere(s) => s.hit(ray, ray_t),
+            GeometricPrimitive::Quad(q) => q.hit(ray, ray_t),
+            GeometricPrimitive::Triangle(t) => t.hit(ray, ray_t),
+            GeometricPrimitive::Mesh(m) => m.hit(ray, ray_t),
             GeometricPrimitive::Other(o) => o.hit(ray, ray_t),
         }
     }
+
+    fn bounding_box(&self) -> Aabb<T> {
+        match self {
+            GeometricPrimitive::Sphere(s) => s.bounding_box(),
+            GeometricPrimitive::Quad(q) => q.bounding_box(),
+            GeometricPrimitive::Triangle(t) => t.bounding_box(),
+            GeometricPrimitive::Mesh(m) => m.bounding_box(),
+            GeometricPrimitive::Other(o) => o.bounding_box(),
+        }
+    }
 }
 
-impl<'a, T, U> Hittable<T, U> for &[GeometricPrimitive<'a, T, U>]
+impl<T, U> Hittable<T, U> for &[GeometricPrimitive<T, U>]
 where
-    T: Copy,
-    Sphere<'a, T, U>: Hittable<T, U>,
+    T: Float,
+    Sphere<T, U>: Hittable<T, U>,
+    Quad<T, U>: Hittable<T, U>,
+    Triangle<T, U>: Hittable<T, U>,
+    TriangleMesh<T, U>: Hittable<T, U>,
 {
-    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<T, U>> {
+    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<'_, T, U>> {
         let mut result = None;
         let mut closest = ray_t.max;
         for object in self.iter() {
@@ -94,4 +342,44 @@ where
         }
         result
     }
+
+    fn bounding_box(&self) -> Aabb<T> {
+        self.iter().fold(Aabb::empty(), |bbox, object| {
+            Aabb::surrounding(&bbox, &object.bounding_box())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sphere_uv_at_the_poles() {
+        let (_, v) = Sphere::<f64, f32>::sphere_uv(Vector3::new(0.0, -1.0, 0.0));
+        assert_eq!(v, 0.0);
+        let (_, v) = Sphere::<f64, f32>::sphere_uv(Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(v, 1.0);
+        // rounding can push a normalized pole just past 1
+        let (u, v) = Sphere::<f64, f32>::sphere_uv(Vector3::new(0.0, 1.0 + 1e-15, 0.0));
+        assert!(!u.is_nan());
+        assert_eq!(v, 1.0);
+        let (_, v) = Sphere::<f64, f32>::sphere_uv(Vector3::new(0.0, -1.0 - 1e-15, 0.0));
+        assert_eq!(v, 0.0);
+    }
+
+    #[test]
+    fn sphere_uv_around_the_equator() {
+        let cases = [
+            ((-1.0, 0.0, 0.0), 0.0),
+            ((0.0, 0.0, 1.0), 0.25),
+            ((1.0, 0.0, 0.0), 0.5),
+            ((0.0, 0.0, -1.0), 0.75),
+        ];
+        for ((x, y, z), expected) in cases {
+            let (u, v) = Sphere::<f64, f32>::sphere_uv(Vector3::new(x, y, z));
+            assert!((u - expected).abs() < 1e-12, "({x}, {y}, {z}) gave u {u}");
+            assert!((v - 0.5).abs() < 1e-12);
+        }
+    }
 }
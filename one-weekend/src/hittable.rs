@@ -1,3 +1,4 @@
+use math::Aabb;
 use math::Float;
 use math::Interval;
 
@@ -12,14 +13,18 @@ pub struct HitRecord<'a, T, U> {
     pub normal: Vector3<T>,
     pub material: &'a dyn Material<T, U>,
     pub t: T,
+    /// surface coordinates of the hit point
+    pub u: T,
+    pub v: T,
     pub front_face: bool,
 }
 
-pub trait Hittable<T, U> {
-    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<T, U>>;
+pub trait Hittable<T, U>: Send + Sync {
+    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<'_, T, U>>;
+    fn bounding_box(&self) -> Aabb<T>;
 }
 
-impl<'a, 'b, T, U> HitRecord<'a, T, U>
+impl<'a, T, U> HitRecord<'a, T, U>
 where
     T: Float,
     U: Float,
@@ -30,6 +35,8 @@ where
         outward_normal: Vector3<T>,
         material: &'a dyn Material<T, U>,
         t: T,
+        u: T,
+        v: T,
     ) -> HitRecord<'a, T, U> {
         let zero = T::constant(0.0);
         let front_face = ray.direction().dot(outward_normal) < zero;
@@ -42,6 +49,8 @@ where
             normal,
             material,
             t,
+            u,
+            v,
             front_face,
         }
     }
@@ -49,9 +58,9 @@ where
 
 impl<T, U> Hittable<T, U> for &[Box<dyn Hittable<T, U>>]
 where
-    T: Copy,
+    T: Float,
 {
-    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<T, U>> {
+    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<'_, T, U>> {
         let mut result = None;
         let mut closest = ray_t.max;
         for object in self.iter() {
@@ -63,4 +72,10 @@ where
         }
         result
     }
+
+    fn bounding_box(&self) -> Aabb<T> {
+        self.iter().fold(Aabb::empty(), |bbox, object| {
+            Aabb::surrounding(&bbox, &object.bounding_box())
+        })
+    }
 }
@@ -0,0 +1,111 @@
+use external::stb::{self, LoadError};
+use math::{Float, RGB};
+use num::traits::AsPrimitive;
+
+/// What to do with sample coordinates outside of [0,1]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    /// repeat the edge pixels
+    Clamp,
+    /// tile the image
+    Repeat,
+}
+
+/// Linear colour image, stored row by row from the top
+#[derive(Clone)]
+pub struct Image<U> {
+    width: u32,
+    height: u32,
+    pixels: Vec<RGB<U>>,
+}
+
+impl<U> Image<U>
+where
+    U: Float,
+{
+    /// # Panics
+    ///
+    /// When the number of pixels is not width * height
+    pub fn new(width: u32, height: u32, pixels: Vec<RGB<U>>) -> Image<U> {
+        assert!(width as usize * height as usize == pixels.len());
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Reads a PNG, JPEG, BMP, TGA, ... file or a Radiance HDR file.
+    ///
+    /// 8-bit images are decoded from sRGB, HDR files are already linear and keep values
+    /// above 1. Alpha is dropped.
+    pub fn load(path: &str) -> Result<Image<U>, LoadError> {
+        if stb::is_hdr(path)? {
+            let loaded = stb::load_f32(path, 3)?;
+            let pixels = loaded
+                .data
+                .chunks_exact(3)
+                .map(|c| {
+                    RGB::new(
+                        U::from_f32(c[0]).unwrap(),
+                        U::from_f32(c[1]).unwrap(),
+                        U::from_f32(c[2]).unwrap(),
+                    )
+                })
+                .collect();
+            Ok(Image::new(loaded.width, loaded.height, pixels))
+        } else {
+            let loaded = stb::load(path, 3)?;
+            let pixels = RGB::from_srgb_array(&loaded.data);
+            Ok(Image::new(loaded.width, loaded.height, pixels))
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[RGB<U>] {
+        &self.pixels
+    }
+
+    /// Pixel at column x and row y, coordinates outside the image are wrapped
+    pub fn pixel(&self, x: i64, y: i64, wrap: Wrap) -> RGB<U> {
+        let wrap = |i: i64, size: u32| match wrap {
+            Wrap::Clamp => i.clamp(0, size as i64 - 1),
+            Wrap::Repeat => i.rem_euclid(size as i64),
+        };
+        let x = wrap(x, self.width);
+        let y = wrap(y, self.height);
+        self.pixels[(y * self.width as i64 + x) as usize]
+    }
+
+    /// Bilinear sample at texture coordinates (u, v), with v = 0 at the bottom row.
+    /// Images without pixels sample as black.
+    pub fn sample<T>(&self, u: T, v: T, wrap: Wrap) -> RGB<U>
+    where
+        T: Float + AsPrimitive<U>,
+        U: 'static,
+    {
+        if self.pixels.is_empty() {
+            return RGB::black();
+        }
+
+        // pixel centers are at half coordinates
+        let x = u * T::from_u32(self.width).unwrap() - T::constant(0.5);
+        let y = (T::one() - v) * T::from_u32(self.height).unwrap() - T::constant(0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy): (U, U) = ((x - x0).as_(), (y - y0).as_());
+        let x0 = x0.to_i64().unwrap_or(0);
+        let y0 = y0.to_i64().unwrap_or(0);
+
+        let top = self.pixel(x0, y0, wrap) * (U::one() - fx) + self.pixel(x0 + 1, y0, wrap) * fx;
+        let bottom =
+            self.pixel(x0, y0 + 1, wrap) * (U::one() - fx) + self.pixel(x0 + 1, y0 + 1, wrap) * fx;
+        top * (U::one() - fy) + bottom * fy
+    }
+}
@@ -1,11 +1,32 @@
+mod background;
+mod bvh;
 mod camera;
 mod geometric_primitives;
 mod hittable;
+mod image;
 mod materials;
+mod obj;
+mod output;
 mod rng_adapter;
+mod scene;
+mod scene_file;
+pub mod scenes;
+mod textures;
+mod tone_mapping;
+mod triangle_mesh;
 
+pub use background::*;
+pub use bvh::*;
 pub use camera::*;
 pub use geometric_primitives::*;
 pub use hittable::*;
+pub use image::*;
 pub use materials::*;
+pub use obj::*;
+pub use output::*;
 pub use rng_adapter::*;
+pub use scene::*;
+pub use scene_file::*;
+pub use textures::*;
+pub use tone_mapping::*;
+pub use triangle_mesh::*;
@@ -1,146 +1,351 @@
+use std::str::FromStr;
+
 use external::stb;
-use math::*;
-use noise::RandomNumberGenerator;
+use math::{Degrees, Dither};
 use one_weekend::{
-    Camera, Dielectric, GeometricPrimitive, Lambertian, Material, Metal, RNGAdapter, Sphere,
+    scenes, write_image_as, Camera, CameraError, CameraSettings, DisplaySettings, ImageFormat,
+    OutputError, Projection, RenderSettings, SceneFile, ToneMapping,
 };
 
-enum MaterialIndex {
-    Lambertian(usize),
-    Metal(usize),
-    Dielectric,
+const USAGE: &str = "usage: one-weekend [OPTIONS], see --help";
+
+fn help() -> String {
+    format!(
+        "\
+Renders a scene with a path tracer and saves it as an image
+
+usage: one-weekend [OPTIONS]
+
+options:
+  -o, --output PATH       image to write, default image.png, or image.EXT with --format
+      --format NAME       {formats}, default from the extension of the output
+      --quality N         JPEG quality from 1 to 100, default {quality}
+      --width N           image width in pixels, default 1200
+      --height N          image height in pixels, default from the width and a 16:9 ratio
+  -s, --samples N         samples per pixel, default 500
+  -d, --max-depth N       maximum number of bounces of a ray, default 50
+                          the four options above default to the settings of the scene when it has them
+      --seed N            seed for the scene and the render, default 42
+  -j, --threads N         render threads, default 0 for one per core
+      --scene NAME        {scenes}, default {scene}
+      --earth-map PATH    equirectangular image of the Earth for the {earth_scenes} scenes,
+                          default earthmap.jpg
+  -f, --scene-file PATH   render a scene description instead of a built-in scene
+      --projection NAME   {projections}, default from the scene
+                          only perspective keeps the defocus blur of the scene
+      --exposure STOPS    brightens the 8-bit formats by 2^STOPS, default 0
+      --tone-map NAME     {tone_maps}, default none
+      --white-point L     luminance mapped to white by reinhard-extended, default 4
+      --dither NAME       {dithers}, default none
+                          noise added to the 8-bit formats before rounding, hides banding
+  -h, --help              print this help",
+        formats = ImageFormat::NAMES.join(", "),
+        quality = ImageFormat::DEFAULT_JPEG_QUALITY,
+        scenes = scenes::NAMES.join(", "),
+        scene = scenes::NAMES[0],
+        earth_scenes = scenes::NEEDS_EARTH_MAP.join(" and "),
+        tone_maps = ToneMapping::NAMES.join(", "),
+        projections = Projection::NAMES.join(", "),
+        dithers = Dither::NAMES.join(", "),
+    )
 }
 
-fn main() {
-    // RNG
-
-    let mut rng = RandomNumberGenerator::new(42);
-
-    // Materials
-
-    let material_ground = Lambertian::new(RGB::new(0.5, 0.5, 0.5));
-    let material1 = Dielectric::new(1.5);
-    let material2 = Lambertian::new(RGB::new(0.4, 0.2, 0.1));
-    let material3 = Metal::new(RGB::new(0.7, 0.6, 0.5), 0.0);
-
-    let mut materials_lambertian = Vec::new();
-    let mut materials_metal = Vec::new();
-    let material_dielectric = &material2;
-
-    let mut material_indices = Vec::with_capacity(500);
-    for _ in 0..500 {
-        let choose_mat = rng.next_f32();
-        if choose_mat < 0.8 {
-            // lambertian
-            material_indices.push(MaterialIndex::Lambertian(materials_lambertian.len()));
-            let albedo =
-                RGB::random(&mut RNGAdapter(&mut rng)) * RGB::random(&mut RNGAdapter(&mut rng));
-            materials_lambertian.push(Lambertian::new(albedo));
-        } else if choose_mat < 0.95 {
-            material_indices.push(MaterialIndex::Metal(materials_metal.len()));
-            let albedo = RGB::random_range(&mut RNGAdapter(&mut rng), 0.5, 1.0);
-            let fuzz = rng.next_range_f32(0.0, 0.5);
-            materials_metal.push(Metal::new(albedo, fuzz));
-        } else {
-            material_indices.push(MaterialIndex::Dielectric);
+/// Everything that can be set from the command line
+struct Options {
+    output: String,
+    format: ImageFormat,
+    width: Option<u32>,
+    height: Option<u32>,
+    samples_per_pixel: Option<u32>,
+    max_depth: Option<u32>,
+    seed: u32,
+    thread_count: usize,
+    scene: &'static str,
+    earth_map: String,
+    scene_file: Option<String>,
+    projection: Option<Projection>,
+    display: DisplaySettings,
+}
+
+/// Reads the options from the arguments, prints the help and exits when asked for it
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    fn number<N: FromStr>(arg: &str, value: String) -> Result<N, String> {
+        value
+            .parse()
+            .map_err(|_| format!("invalid value {value} for {arg}"))
+    }
+
+    let mut output = None;
+    let mut format = None;
+    let mut quality = None;
+    let mut width = None;
+    let mut height = None;
+    let mut samples_per_pixel = None;
+    let mut max_depth = None;
+    let mut seed = 42;
+    let mut thread_count = 0;
+    let mut scene = None;
+    let mut earth_map = "earthmap.jpg".to_string();
+    let mut scene_file = None;
+    let mut projection = None;
+    let mut display = DisplaySettings::default();
+    let mut white_point = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", help());
+                std::process::exit(0);
+            }
+            "-o" | "--output" => output = Some(value()?),
+            "--format" => {
+                let value = value()?;
+                format = Some(ImageFormat::from_name(&value).ok_or_else(|| {
+                    format!(
+                        "unknown format {value}, expected one of {}",
+                        ImageFormat::NAMES.join(", ")
+                    )
+                })?);
+            }
+            "--quality" => quality = Some(number::<u32>(&arg, value()?)?),
+            "--width" => width = Some(number(&arg, value()?)?),
+            "--height" => height = Some(number(&arg, value()?)?),
+            "-s" | "--samples" => samples_per_pixel = Some(number(&arg, value()?)?),
+            "-d" | "--max-depth" => max_depth = Some(number(&arg, value()?)?),
+            "--seed" => seed = number(&arg, value()?)?,
+            "-j" | "--threads" => thread_count = number(&arg, value()?)?,
+            "--scene" => {
+                let value = value()?;
+                scene = Some(
+                    scenes::NAMES
+                        .into_iter()
+                        .find(|&name| name == value)
+                        .ok_or_else(|| {
+                            format!(
+                                "unknown scene {value}, expected one of {}",
+                                scenes::NAMES.join(", ")
+                            )
+                        })?,
+                );
+            }
+            "--projection" => {
+                let value = value()?;
+                projection = Some(Projection::from_name(&value).ok_or_else(|| {
+                    format!(
+                        "unknown projection {value}, expected one of {}",
+                        Projection::NAMES.join(", ")
+                    )
+                })?);
+            }
+            "--exposure" => display.exposure = number(&arg, value()?)?,
+            "--tone-map" => {
+                let value = value()?;
+                display.tone_mapping = ToneMapping::from_name(&value).ok_or_else(|| {
+                    format!(
+                        "unknown tone mapping {value}, expected one of {}",
+                        ToneMapping::NAMES.join(", ")
+                    )
+                })?;
+            }
+            "--dither" => {
+                let value = value()?;
+                display.dither = Dither::from_name(&value).ok_or_else(|| {
+                    format!(
+                        "unknown dither {value}, expected one of {}",
+                        Dither::NAMES.join(", ")
+                    )
+                })?;
+            }
+            "--earth-map" => earth_map = value()?,
+            "-f" | "--scene-file" => scene_file = Some(value()?),
+            "--white-point" => white_point = Some(number(&arg, value()?)?),
+            _ => return Err(format!("unknown argument {arg}")),
         }
     }
 
-    let materials_lambertian = materials_lambertian;
-    let materials_metal = materials_metal;
-    let material_indices = material_indices;
-
-    // World
-
-    let mut world: Vec<GeometricPrimitive<f64, f32>> = Vec::new();
-    world.push(GeometricPrimitive::Sphere(Sphere::new(
-        Point3 {
-            x: 0.0,
-            y: -1000.0,
-            z: 0.0,
-        },
-        1000.0,
-        &material_ground,
-    )));
-    world.push(GeometricPrimitive::Sphere(Sphere::new(
-        Point3 {
-            x: 0.0,
-            y: 1.0,
-            z: 0.0,
-        },
-        1.0,
-        &material1,
-    )));
-    world.push(GeometricPrimitive::Sphere(Sphere::new(
-        Point3 {
-            x: -4.0,
-            y: 1.0,
-            z: 0.0,
-        },
-        1.0,
-        &material2,
-    )));
-    world.push(GeometricPrimitive::Sphere(Sphere::new(
-        Point3 {
-            x: 4.0,
-            y: 1.0,
-            z: 0.0,
-        },
-        1.0,
-        &material3,
-    )));
-
-    let mut idx = 0;
-    for a in -11..11 {
-        for b in -11..11 {
-            let center = Point3::new(
-                a as f64 + rng.next_range_f64(0.0, 0.9),
-                0.2,
-                b as f64 + rng.next_range_f64(0.0, 0.9),
-            );
-            if (center - Point3::new(4.0, 0.2, 0.0)).length_squared() > 0.9 * 0.9 {
-                let material: &dyn Material<f64, f32> = match material_indices[idx] {
-                    MaterialIndex::Lambertian(idx) => &materials_lambertian[idx],
-                    MaterialIndex::Metal(idx) => &materials_metal[idx],
-                    MaterialIndex::Dielectric => material_dielectric,
-                };
-                let geometry = Sphere::new(center, 0.2, material);
-                world.push(GeometricPrimitive::Sphere(geometry));
-                idx = idx + 1;
+    // the format, and whether this build can write it, are checked now rather than
+    // after a long render
+    let output = output.unwrap_or_else(|| match format {
+        Some(format) => format!("image.{}", format.extension()),
+        None => "image.png".to_string(),
+    });
+    let mut format = format
+        .or_else(|| ImageFormat::from_path(&output))
+        .ok_or_else(|| OutputError::UnknownFormat(output.clone()).to_string())?;
+    format.check_writable().map_err(|error| {
+        OutputError::Write {
+            path: output.clone(),
+            error,
+        }
+        .to_string()
+    })?;
+    if let Some(quality) = quality {
+        match format {
+            ImageFormat::Jpg { .. } if (1..=100).contains(&quality) => {
+                format = ImageFormat::Jpg {
+                    quality: quality as u8,
+                }
+            }
+            ImageFormat::Jpg { .. } => {
+                return Err(format!("JPEG quality {quality} is not in 1 to 100"))
             }
+            _ => return Err("--quality only applies to jpg".to_string()),
         }
     }
 
-    let world = &world[..];
-
-    // Camera
-
-    let camera = Camera::new(
-        16.0 / 9.0,
-        1200,
-        500,
-        50,
-        Degrees(20.0),
-        Point3::new(13.0, 2.0, 3.0),
-        Point3::new(0.0, 0.0, 0.0),
-        Vector3::new(0.0, 1.0, 0.0),
-        Degrees(0.6),
-        10.0,
-    );
+    if let Some(white) = white_point {
+        match display.tone_mapping {
+            ToneMapping::ReinhardExtended { .. } => {
+                display.tone_mapping = ToneMapping::ReinhardExtended { white }
+            }
+            _ => return Err("--white-point only applies to reinhard-extended".to_string()),
+        }
+    }
+
+    if width == Some(0) || height == Some(0) {
+        return Err("image size must be at least 1x1".to_string());
+    }
+    if samples_per_pixel == Some(0) {
+        return Err("--samples must be at least 1".to_string());
+    }
+    if scene.is_some() && scene_file.is_some() {
+        return Err("--scene and --scene-file cannot be used together".to_string());
+    }
+    let scene = scene.unwrap_or(scenes::NAMES[0]);
+    if scenes::NEEDS_EARTH_MAP.contains(&scene) && !stb::LOADS_IMAGES {
+        return Err(format!(
+            "the {scene} scene needs to load {earth_map}, images cannot be loaded in the pure-rust build"
+        ));
+    }
+
+    Ok(Options {
+        output,
+        format,
+        width,
+        height,
+        samples_per_pixel,
+        max_depth,
+        seed,
+        thread_count,
+        scene,
+        earth_map,
+        scene_file,
+        projection,
+        display,
+    })
+}
+
+impl Options {
+    /// Size of the image, the command line overrides the scene file. When only one side
+    /// is known the other follows the aspect ratio of the file, or 16:9.
+    fn image_size(&self, file: &RenderSettings) -> (u32, u32) {
+        let aspect_ratio = match (file.width, file.height) {
+            (Some(width), Some(height)) => width as f64 / height as f64,
+            _ => 16.0 / 9.0,
+        };
+        let size = match (self.width, self.height) {
+            (None, None) => (file.width, file.height),
+            size => size,
+        };
+        match size {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, ((width as f64 / aspect_ratio).round() as u32).max(1)),
+            (None, Some(height)) => (
+                ((height as f64 * aspect_ratio).round() as u32).max(1),
+                height,
+            ),
+            (None, None) => (1200, ((1200.0 / aspect_ratio).round() as u32).max(1)),
+        }
+    }
 
-    // Render
+    fn camera(
+        &self,
+        settings: &CameraSettings<f64>,
+        file: &RenderSettings,
+    ) -> Result<Camera<f64>, CameraError> {
+        let (width, height) = self.image_size(file);
+        let settings = match self.projection {
+            // the lens of the scene is meant for its perspective view
+            Some(projection) if projection != Projection::Perspective => CameraSettings {
+                projection,
+                defocus_angle: Degrees(0.0),
+                ..*settings
+            },
+            Some(projection) => CameraSettings {
+                projection,
+                ..*settings
+            },
+            None => *settings,
+        };
+        settings.camera(
+            width,
+            height,
+            self.samples_per_pixel
+                .or(file.samples_per_pixel)
+                .unwrap_or(500),
+            self.max_depth.or(file.max_depth).unwrap_or(50),
+        )
+    }
+}
 
-    let data = camera.render(&world, &mut rng);
+fn main() {
+    let options = parse_options(std::env::args().skip(1)).unwrap_or_else(|message| {
+        eprintln!("{message}\n{USAGE}");
+        std::process::exit(2);
+    });
+
+    let (scene, camera, render) = match &options.scene_file {
+        Some(path) => {
+            let file = SceneFile::load(path).unwrap_or_else(|error| {
+                eprintln!("{error}");
+                std::process::exit(1);
+            });
+            for warning in &file.warnings {
+                eprintln!("warning: {warning}");
+            }
+            (file.scene, file.camera, file.render)
+        }
+        None => {
+            let builtin = scenes::from_name(options.scene, options.seed, &options.earth_map)
+                .unwrap()
+                .unwrap_or_else(|error| {
+                    eprintln!(
+                        "{error}\nthe {} scene needs an image of the Earth, see --earth-map",
+                        options.scene
+                    );
+                    std::process::exit(1);
+                });
+            (builtin.scene, builtin.camera, builtin.render)
+        }
+    };
+
+    let camera = options.camera(&camera, &render).unwrap_or_else(|error| {
+        eprintln!("{error}");
+        std::process::exit(1);
+    });
+    let data = camera.render(
+        &scene,
+        scene.background(),
+        options.seed,
+        options.thread_count,
+    );
 
     eprint!("\rWriting image            ");
 
-    stb::write_png(
-        "image.png",
+    if let Err(error) = write_image_as(
+        &options.output,
+        options.format,
         camera.get_image_width(),
         camera.get_image_height(),
-        3,
-        &RGB::to_srgb_array(&data),
-    )
-    .unwrap();
+        &data,
+        &options.display,
+    ) {
+        eprintln!("\n{error}");
+        std::process::exit(1);
+    }
     eprint!("\rDONE            \n");
 }
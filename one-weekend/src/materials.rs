@@ -1,8 +1,8 @@
-use math::{Float, RandomSource, Ray, Vector3, RGB};
+use math::{Float, Point3, RandomSource, Ray, Vector3, RGB};
 
 use crate::*;
 
-pub trait Material<T, U>
+pub trait Material<T, U>: Send + Sync
 where
     T: Float,
     U: Float,
@@ -13,20 +13,33 @@ where
         ray: &Ray<T>,
         hit_record: &HitRecord<T, U>,
     ) -> Option<(Ray<T>, RGB<U>)>;
+
+    /// Light given off by the surface at the hit point, only light sources emit
+    fn emitted(&self, _u: T, _v: T, _point: Point3<T>) -> RGB<U> {
+        RGB::black()
+    }
 }
 
-pub struct Lambertian<U>(RGB<U>);
+/// Diffuse surface, the albedo is looked up in a texture
+pub struct Lambertian<Tex>(Tex);
 
-impl<U> Lambertian<U> {
+impl<U> Lambertian<SolidColor<U>> {
     pub fn new(albedo: RGB<U>) -> Self {
+        Self(SolidColor::new(albedo))
+    }
+}
+
+impl<Tex> Lambertian<Tex> {
+    pub fn from_texture(albedo: Tex) -> Self {
         Self(albedo)
     }
 }
 
-impl<T, U> Material<T, U> for Lambertian<U>
+impl<T, U, Tex> Material<T, U> for Lambertian<Tex>
 where
     T: Float,
     U: Float,
+    Tex: Texture<T, U>,
 {
     fn scatter(
         &self,
@@ -41,25 +54,33 @@ where
             direction = hit_record.normal;
         }
 
-        Some((Ray::new(hit_record.point, direction), self.0))
+        let attenuation = self.0.value(hit_record.u, hit_record.v, hit_record.point);
+        Some((Ray::new(hit_record.point, direction), attenuation))
     }
 }
 
-pub struct Metal<U> {
-    albedo: RGB<U>,
+pub struct Metal<Tex, U> {
+    albedo: Tex,
     fuzz: U,
 }
 
-impl<U> Metal<U> {
+impl<U> Metal<SolidColor<U>, U> {
     pub fn new(albedo: RGB<U>, fuzz: U) -> Self {
+        Self::from_texture(SolidColor::new(albedo), fuzz)
+    }
+}
+
+impl<Tex, U> Metal<Tex, U> {
+    pub fn from_texture(albedo: Tex, fuzz: U) -> Self {
         Self { albedo, fuzz }
     }
 }
 
-impl<T, U> Material<T, U> for Metal<U>
+impl<T, U, Tex> Material<T, U> for Metal<Tex, U>
 where
     T: Float,
     U: Float + Into<T>,
+    Tex: Texture<T, U>,
 {
     fn scatter(
         &self,
@@ -72,7 +93,10 @@ where
             + Vector3::random_unit_vector(&mut DynAdapter(rng)) * self.fuzz.into())
         .unit_vector();
         if scattered.dot(hit_record.normal) > T::constant(0.0) {
-            Some((Ray::new(hit_record.point, scattered), self.albedo))
+            let attenuation = self
+                .albedo
+                .value(hit_record.u, hit_record.v, hit_record.point);
+            Some((Ray::new(hit_record.point, scattered), attenuation))
         } else {
             None
         }
@@ -129,3 +153,38 @@ where
         Some((Ray::new(hit_record.point, direction), RGB::white()))
     }
 }
+
+/// Emits light evenly in all directions and absorbs everything that hits it
+pub struct DiffuseLight<Tex>(Tex);
+
+impl<U> DiffuseLight<SolidColor<U>> {
+    pub fn new(emit: RGB<U>) -> Self {
+        Self(SolidColor::new(emit))
+    }
+}
+
+impl<Tex> DiffuseLight<Tex> {
+    pub fn from_texture(emit: Tex) -> Self {
+        Self(emit)
+    }
+}
+
+impl<T, U, Tex> Material<T, U> for DiffuseLight<Tex>
+where
+    T: Float,
+    U: Float,
+    Tex: Texture<T, U>,
+{
+    fn scatter(
+        &self,
+        _: &mut dyn RandomSource<T>,
+        _: &Ray<T>,
+        _: &HitRecord<T, U>,
+    ) -> Option<(Ray<T>, RGB<U>)> {
+        None
+    }
+
+    fn emitted(&self, u: T, v: T, point: Point3<T>) -> RGB<U> {
+        self.0.value(u, v, point)
+    }
+}
@@ -0,0 +1,772 @@
+//! Wavefront OBJ and MTL import
+//!
+//! Only the geometry statements (`v`, `vt`, `vn`, `f`, `g`, `o`) and the material
+//! statements this renderer can represent are read, everything else is skipped.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::SplitWhitespace;
+use std::sync::Arc;
+
+use math::{Float, Point3, RandomSource, Ray, Vector3, RGB};
+
+use crate::{
+    Dielectric, DiffuseLight, HitRecord, Lambertian, Material, MeshTriangle, Metal, SolidColor,
+    TriangleMesh,
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { error, .. } => Some(error),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+/// Material read from an MTL file, mapped to the closest material we support
+pub struct ObjMaterial<U> {
+    pub name: String,
+    pub kind: ObjMaterialKind<U>,
+}
+
+pub enum ObjMaterialKind<U> {
+    Lambertian(Lambertian<SolidColor<U>>),
+    Metal(Metal<SolidColor<U>, U>),
+    Dielectric(Dielectric<U>),
+    DiffuseLight(DiffuseLight<SolidColor<U>>),
+}
+
+impl<T, U> Material<T, U> for ObjMaterial<U>
+where
+    T: Float,
+    U: Float + Into<T>,
+{
+    fn scatter(
+        &self,
+        rng: &mut dyn RandomSource<T>,
+        ray: &Ray<T>,
+        hit_record: &HitRecord<T, U>,
+    ) -> Option<(Ray<T>, RGB<U>)> {
+        match &self.kind {
+            ObjMaterialKind::Lambertian(m) => m.scatter(rng, ray, hit_record),
+            ObjMaterialKind::Metal(m) => m.scatter(rng, ray, hit_record),
+            ObjMaterialKind::Dielectric(m) => m.scatter(rng, ray, hit_record),
+            ObjMaterialKind::DiffuseLight(m) => m.scatter(rng, ray, hit_record),
+        }
+    }
+
+    fn emitted(&self, u: T, v: T, point: Point3<T>) -> RGB<U> {
+        match &self.kind {
+            ObjMaterialKind::Lambertian(m) => m.emitted(u, v, point),
+            ObjMaterialKind::Metal(m) => m.emitted(u, v, point),
+            ObjMaterialKind::Dielectric(m) => m.emitted(u, v, point),
+            ObjMaterialKind::DiffuseLight(m) => m.emitted(u, v, point),
+        }
+    }
+}
+
+/// Triangles of one group (`g` or `o`) that share a material.
+/// Indices point into the buffers of the whole scene.
+pub struct ObjGroup {
+    pub name: String,
+    pub material: Option<usize>,
+    pub triangles: Vec<MeshTriangle>,
+}
+
+pub struct ObjScene<T, U> {
+    pub positions: Vec<Point3<T>>,
+    pub normals: Vec<Vector3<T>>,
+    pub uvs: Vec<(T, T)>,
+    pub groups: Vec<ObjGroup>,
+    pub materials: Vec<Arc<ObjMaterial<U>>>,
+    /// Problems that did not stop the load, as `path:line: message`
+    pub warnings: Vec<String>,
+}
+
+impl<T, U> ObjScene<T, U>
+where
+    T: Float,
+    U: Float,
+{
+    /// Loads an OBJ file, and the MTL files it references relative to its folder
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ObjScene<T, U>, ObjError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|error| ObjError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Self::parse(&source, path)
+    }
+
+    /// Parses the contents of an OBJ file, `path` is used to find MTL files and in errors.
+    ///
+    /// Faces after a `usemtl` naming a material none of the MTL files define get the
+    /// default material of [`ObjScene::meshes`], with a warning.
+    pub fn parse(source: &str, path: &Path) -> Result<ObjScene<T, U>, ObjError> {
+        let mut scene = ObjScene {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            groups: Vec::new(),
+            materials: Vec::new(),
+            warnings: Vec::new(),
+        };
+        let mut group_name = String::new();
+        let mut material = None;
+
+        for (line_index, line) in source.lines().enumerate() {
+            let mut parser = LineParser::new(line, line_index + 1, path);
+            let Some(keyword) = parser.keyword() else {
+                continue;
+            };
+            match keyword {
+                "v" => {
+                    let [x, y, z] = parser.floats::<T, 3>()?;
+                    scene.positions.push(Point3::new(x, y, z));
+                }
+                "vn" => {
+                    let [x, y, z] = parser.floats::<T, 3>()?;
+                    scene.normals.push(Vector3::new(x, y, z));
+                }
+                "vt" => {
+                    let [u, v] = parser.floats::<T, 2>()?;
+                    scene.uvs.push((u, v));
+                }
+                "f" => {
+                    let face = parser.face(&scene)?;
+                    let triangles = triangulate(&scene.positions, &face);
+                    scene.current_group(&group_name, material).extend(triangles);
+                }
+                "g" | "o" => {
+                    group_name = parser.rest().to_string();
+                }
+                "usemtl" => {
+                    let name = parser.rest();
+                    material = scene.materials.iter().position(|m| m.name == name);
+                    if material.is_none() {
+                        let message = format!("unknown material '{}', using the default", name);
+                        scene.warnings.push(parser.error(message).to_string());
+                    }
+                }
+                "mtllib" => {
+                    let folder = path.parent().unwrap_or(Path::new(""));
+                    for file in parser.rest().split_whitespace() {
+                        scene.load_mtl(&folder.join(file), &parser)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        scene.groups.retain(|group| !group.triangles.is_empty());
+        Ok(scene)
+    }
+
+    fn current_group(&mut self, name: &str, material: Option<usize>) -> &mut Vec<MeshTriangle> {
+        let is_current = self
+            .groups
+            .last()
+            .is_some_and(|group| group.name == name && group.material == material);
+        if !is_current {
+            self.groups.push(ObjGroup {
+                name: name.to_string(),
+                material,
+                triangles: Vec::new(),
+            });
+        }
+        &mut self.groups.last_mut().unwrap().triangles
+    }
+
+    fn load_mtl(&mut self, path: &Path, mtllib: &LineParser) -> Result<(), ObjError> {
+        let source = fs::read_to_string(path)
+            .map_err(|error| mtllib.error(format!("cannot read {}: {}", path.display(), error)))?;
+
+        let mut current: Option<MtlDescription<U>> = None;
+        for (line_index, line) in source.lines().enumerate() {
+            let mut parser = LineParser::new(line, line_index + 1, path);
+            let Some(keyword) = parser.keyword() else {
+                continue;
+            };
+            if keyword == "newmtl" {
+                if let Some(description) = current.take() {
+                    self.materials.push(Arc::new(description.into_material()));
+                }
+                current = Some(MtlDescription::new(parser.rest()));
+                continue;
+            }
+
+            let Some(description) = current.as_mut() else {
+                return Err(parser.error(format!("'{}' before any newmtl", keyword)));
+            };
+            match keyword {
+                "Kd" => description.diffuse = parser.color()?,
+                "Ks" => description.specular = Some(parser.color()?),
+                "Ke" => description.emission = parser.color()?,
+                "Ns" => description.shininess = parser.floats::<U, 1>()?[0],
+                "Ni" => description.index_of_refraction = parser.floats::<U, 1>()?[0],
+                "d" => description.dissolve = parser.floats::<U, 1>()?[0],
+                "Tr" => description.dissolve = U::one() - parser.floats::<U, 1>()?[0],
+                "illum" => description.illumination = parser.integer()?,
+                _ => {}
+            }
+        }
+        if let Some(description) = current {
+            self.materials.push(Arc::new(description.into_material()));
+        }
+        Ok(())
+    }
+
+    /// Builds one mesh per group, with only the vertices that group uses.
+    /// Groups without a material use `default_material`.
+    pub fn meshes(&self, default_material: Arc<dyn Material<T, U>>) -> Vec<TriangleMesh<T, U>>
+    where
+        T: 'static,
+        U: 'static + Into<T>,
+    {
+        self.groups
+            .iter()
+            .map(|group| {
+                let material: Arc<dyn Material<T, U>> = match group.material {
+                    Some(index) => self.materials[index].clone(),
+                    None => default_material.clone(),
+                };
+
+                let mut positions = Remap::new(&self.positions);
+                let mut normals = Remap::new(&self.normals);
+                let mut uvs = Remap::new(&self.uvs);
+                let triangles = group
+                    .triangles
+                    .iter()
+                    .map(|triangle| MeshTriangle {
+                        positions: triangle.positions.map(|i| positions.index(i)),
+                        normals: triangle.normals.map(|n| n.map(|i| normals.index(i))),
+                        uvs: triangle.uvs.map(|n| n.map(|i| uvs.index(i))),
+                    })
+                    .collect();
+
+                TriangleMesh::new(
+                    positions.values,
+                    normals.values,
+                    uvs.values,
+                    triangles,
+                    material,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Collects the elements of a shared buffer used by one mesh, giving them new indices
+struct Remap<'a, V> {
+    source: &'a [V],
+    indices: HashMap<usize, usize>,
+    values: Vec<V>,
+}
+
+impl<'a, V: Copy> Remap<'a, V> {
+    fn new(source: &'a [V]) -> Remap<'a, V> {
+        Remap {
+            source,
+            indices: HashMap::new(),
+            values: Vec::new(),
+        }
+    }
+
+    fn index(&mut self, i: usize) -> usize {
+        *self.indices.entry(i).or_insert_with(|| {
+            self.values.push(self.source[i]);
+            self.values.len() - 1
+        })
+    }
+}
+
+struct MtlDescription<U> {
+    name: String,
+    diffuse: RGB<U>,
+    /// Ks, reflective materials use the diffuse colour when it is not given
+    specular: Option<RGB<U>>,
+    emission: RGB<U>,
+    shininess: U,
+    index_of_refraction: U,
+    dissolve: U,
+    illumination: u32,
+}
+
+impl<U: Float> MtlDescription<U> {
+    fn new(name: &str) -> MtlDescription<U> {
+        MtlDescription {
+            name: name.to_string(),
+            diffuse: RGB::new(U::constant(0.8), U::constant(0.8), U::constant(0.8)),
+            specular: None,
+            emission: RGB::black(),
+            shininess: U::constant(0.0),
+            index_of_refraction: U::constant(1.5),
+            dissolve: U::one(),
+            illumination: 2,
+        }
+    }
+
+    /// Emissive materials (Ke) become lights, transparent materials (d < 1, or a refraction
+    /// illumination model) become dielectrics, reflective illumination models become metals
+    /// and anything else is diffuse. Metals take their colour from Ks, or from Kd when
+    /// the file only gives a diffuse colour.
+    fn into_material(self) -> ObjMaterial<U> {
+        let emissive = (0..3).any(|i| self.emission[i] > U::constant(0.0));
+        let transparent = self.dissolve < U::one() || matches!(self.illumination, 4 | 6 | 7 | 9);
+        let reflective = matches!(self.illumination, 3 | 5 | 8);
+        let kind = if emissive {
+            ObjMaterialKind::DiffuseLight(DiffuseLight::new(self.emission))
+        } else if transparent {
+            ObjMaterialKind::Dielectric(Dielectric::new(self.index_of_refraction))
+        } else if reflective {
+            // the Phong exponent goes from 0 (rough) to 1000 (mirror)
+            let fuzz = (U::constant(2.0) / (self.shininess + U::constant(2.0)))
+                .sqrt()
+                .min(U::one());
+            let albedo = self.specular.unwrap_or(self.diffuse);
+            ObjMaterialKind::Metal(Metal::new(albedo, fuzz))
+        } else {
+            ObjMaterialKind::Lambertian(Lambertian::new(self.diffuse))
+        };
+        ObjMaterial {
+            name: self.name,
+            kind,
+        }
+    }
+}
+
+/// Indices of one face corner into the position, texture coordinate and normal buffers
+#[derive(Clone, Copy)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+struct LineParser<'a> {
+    tokens: SplitWhitespace<'a>,
+    line: &'a str,
+    line_number: usize,
+    path: &'a Path,
+}
+
+impl<'a> LineParser<'a> {
+    fn new(line: &'a str, line_number: usize, path: &'a Path) -> LineParser<'a> {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        LineParser {
+            tokens: line.split_whitespace(),
+            line,
+            line_number,
+            path,
+        }
+    }
+
+    fn error(&self, message: String) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line_number,
+            message,
+        }
+    }
+
+    fn keyword(&mut self) -> Option<&'a str> {
+        self.tokens.next()
+    }
+
+    /// Everything after the keyword, for names that may contain spaces
+    fn rest(&self) -> &'a str {
+        let line = self.line.trim();
+        match line.find(char::is_whitespace) {
+            Some(space) => line[space..].trim(),
+            None => "",
+        }
+    }
+
+    fn floats<F: Float, const N: usize>(&mut self) -> Result<[F; N], ObjError> {
+        let mut values = [F::constant(0.0); N];
+        for value in values.iter_mut() {
+            let token = self
+                .tokens
+                .next()
+                .ok_or_else(|| self.error(format!("expected {} numbers", N)))?;
+            *value = token
+                .parse::<f64>()
+                .ok()
+                .and_then(F::from_f64)
+                .ok_or_else(|| self.error(format!("invalid number '{}'", token)))?;
+        }
+        Ok(values)
+    }
+
+    fn color<U: Float>(&mut self) -> Result<RGB<U>, ObjError> {
+        let [r, g, b] = self.floats::<U, 3>()?;
+        Ok(RGB::new(r, g, b))
+    }
+
+    fn integer(&mut self) -> Result<u32, ObjError> {
+        let token = self
+            .tokens
+            .next()
+            .ok_or_else(|| self.error("expected an integer".to_string()))?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("invalid integer '{}'", token)))
+    }
+
+    fn face<T, U>(&mut self, scene: &ObjScene<T, U>) -> Result<Vec<FaceVertex>, ObjError> {
+        let mut face = Vec::new();
+        while let Some(token) = self.tokens.next() {
+            let mut parts = token.split('/');
+            let position = self.index(parts.next(), scene.positions.len(), token)?;
+            let uv = self.optional_index(parts.next(), scene.uvs.len(), token)?;
+            let normal = self.optional_index(parts.next(), scene.normals.len(), token)?;
+            face.push(FaceVertex {
+                position,
+                uv,
+                normal,
+            });
+        }
+        if face.len() < 3 {
+            return Err(self.error("faces need at least 3 vertices".to_string()));
+        }
+        if face.iter().any(|v| v.uv.is_some() != face[0].uv.is_some())
+            || face
+                .iter()
+                .any(|v| v.normal.is_some() != face[0].normal.is_some())
+        {
+            return Err(self.error("face vertices mix different attributes".to_string()));
+        }
+        Ok(face)
+    }
+
+    fn optional_index(
+        &self,
+        part: Option<&str>,
+        count: usize,
+        token: &str,
+    ) -> Result<Option<usize>, ObjError> {
+        match part {
+            None | Some("") => Ok(None),
+            part => self.index(part, count, token).map(Some),
+        }
+    }
+
+    /// OBJ indices start at 1, negative ones count back from the last element read so far
+    fn index(&self, part: Option<&str>, count: usize, token: &str) -> Result<usize, ObjError> {
+        let value: i64 = part
+            .and_then(|p| p.parse().ok())
+            .ok_or_else(|| self.error(format!("invalid face vertex '{}'", token)))?;
+        let index = match value {
+            v if v > 0 => v - 1,
+            v if v < 0 => count as i64 + v,
+            _ => -1,
+        };
+        if index < 0 || index >= count as i64 {
+            return Err(self.error(format!("index out of range in face vertex '{}'", token)));
+        }
+        Ok(index as usize)
+    }
+}
+
+/// Splits a polygon into triangles by ear clipping, so concave faces are handled too.
+/// Works on the projection of the polygon onto the plane of its dominant axis.
+fn triangulate<T: Float>(positions: &[Point3<T>], face: &[FaceVertex]) -> Vec<MeshTriangle> {
+    let triangle = |a: &FaceVertex, b: &FaceVertex, c: &FaceVertex| MeshTriangle {
+        positions: [a.position, b.position, c.position],
+        normals: a
+            .normal
+            .zip(b.normal)
+            .zip(c.normal)
+            .map(|((a, b), c)| [a, b, c]),
+        uvs: a.uv.zip(b.uv).zip(c.uv).map(|((a, b), c)| [a, b, c]),
+    };
+    if face.len() == 3 {
+        return vec![triangle(&face[0], &face[1], &face[2])];
+    }
+
+    // Newell's method gives a robust normal even for concave polygons
+    let mut normal = Vector3::new(T::constant(0.0), T::constant(0.0), T::constant(0.0));
+    for (i, current) in face.iter().enumerate() {
+        let p = positions[current.position];
+        let q = positions[face[(i + 1) % face.len()].position];
+        normal.x += (p.y - q.y) * (p.z + q.z);
+        normal.y += (p.z - q.z) * (p.x + q.x);
+        normal.z += (p.x - q.x) * (p.y + q.y);
+    }
+    let (a0, a1) = match (normal.x.abs(), normal.y.abs(), normal.z.abs()) {
+        (x, y, z) if x >= y && x >= z => (1, 2),
+        (_, y, z) if y >= z => (2, 0),
+        _ => (0, 1),
+    };
+    // keep the winding counter-clockwise in the projected plane
+    let sign = if normal[3 - a0 - a1] < T::constant(0.0) {
+        -T::one()
+    } else {
+        T::one()
+    };
+    let project = |v: &FaceVertex| {
+        let p = positions[v.position];
+        (p[a0], p[a1])
+    };
+    let cross = |a: (T, T), b: (T, T), c: (T, T)| {
+        ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)) * sign
+    };
+
+    let mut remaining: Vec<usize> = (0..face.len()).collect();
+    let mut triangles = Vec::with_capacity(face.len() - 2);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let a = project(&face[remaining[(i + n - 1) % n]]);
+            let b = project(&face[remaining[i]]);
+            let c = project(&face[remaining[(i + 1) % n]]);
+            if cross(a, b, c) <= T::constant(0.0) {
+                return false;
+            }
+            remaining.iter().all(|&other| {
+                let p = project(&face[other]);
+                other == remaining[(i + n - 1) % n]
+                    || other == remaining[i]
+                    || other == remaining[(i + 1) % n]
+                    || cross(a, b, p) < T::constant(0.0)
+                    || cross(b, c, p) < T::constant(0.0)
+                    || cross(c, a, p) < T::constant(0.0)
+            })
+        });
+        // degenerate polygons have no ears left, clip whatever vertex comes first
+        let i = ear.unwrap_or(0);
+        triangles.push(triangle(
+            &face[remaining[(i + n - 1) % n]],
+            &face[remaining[i]],
+            &face[remaining[(i + 1) % n]],
+        ));
+        remaining.remove(i);
+    }
+    triangles.push(triangle(
+        &face[remaining[0]],
+        &face[remaining[1]],
+        &face[remaining[2]],
+    ));
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use noise::RandomNumberGenerator;
+
+    use super::*;
+    use crate::RNGAdapter;
+
+    fn parse(source: &str) -> Result<ObjScene<f64, f32>, ObjError> {
+        ObjScene::parse(source, Path::new("test.obj"))
+    }
+
+    fn parse_error(source: &str) -> String {
+        match parse(source) {
+            Ok(_) => panic!("expected an error"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    /// Writes an MTL file to its own temporary folder and returns the OBJ path next to it
+    fn with_mtl(name: &str, mtl: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("obj-test-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("test.mtl"), mtl).unwrap();
+        folder.join("test.obj")
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn face_vertex_variants() {
+        let source = format!(
+            "{SQUARE}vt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\n\
+             f 1 2 3\nf 1/1 2/2 3/3\nf 1//1 2//1 3//1\nf 1/1/1 2/2/1 3/3/1\n"
+        );
+        let scene = parse(&source).unwrap();
+        assert_eq!(scene.positions.len(), 4);
+        assert_eq!(scene.uvs.len(), 3);
+        assert_eq!(scene.normals.len(), 1);
+        let triangles = &scene.groups[0].triangles;
+        assert_eq!(triangles.len(), 4);
+        for triangle in triangles {
+            assert_eq!(triangle.positions, [0, 1, 2]);
+        }
+        assert_eq!(triangles[0].uvs, None);
+        assert_eq!(triangles[0].normals, None);
+        assert_eq!(triangles[1].uvs, Some([0, 1, 2]));
+        assert_eq!(triangles[1].normals, None);
+        assert_eq!(triangles[2].uvs, None);
+        assert_eq!(triangles[2].normals, Some([0, 0, 0]));
+        assert_eq!(triangles[3].uvs, Some([0, 1, 2]));
+        assert_eq!(triangles[3].normals, Some([0, 0, 0]));
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_vertex() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nf -3 -2 -1\nv 0 1 0\nf -4 -2 -1\n";
+        let scene = parse(source).unwrap();
+        let triangles = &scene.groups[0].triangles;
+        assert_eq!(triangles[0].positions, [0, 1, 2]);
+        assert_eq!(triangles[1].positions, [0, 2, 3]);
+    }
+
+    #[test]
+    fn face_errors_report_their_line() {
+        let cases = [
+            ("f 1 2\n", 5, "faces need at least 3 vertices"),
+            ("f 1 2 5\n", 5, "index out of range in face vertex '5'"),
+            ("f 0 1 2\n", 5, "index out of range in face vertex '0'"),
+            ("f 1 2 -5\n", 5, "index out of range in face vertex '-5'"),
+            ("f 1 x 3\n", 5, "invalid face vertex 'x'"),
+            (
+                "\n# comment\nf 1/1 2 3\n",
+                7,
+                "index out of range in face vertex '1/1'",
+            ),
+            (
+                "vt 0 0\nf 1/1 2 3\n",
+                6,
+                "face vertices mix different attributes",
+            ),
+            ("v 1 2\n", 5, "expected 3 numbers"),
+            ("v 1 2 z\n", 5, "invalid number 'z'"),
+        ];
+        for (tail, line, message) in cases {
+            let error = parse_error(&format!("{SQUARE}{tail}"));
+            assert_eq!(error, format!("test.obj:{line}: {message}"));
+        }
+    }
+
+    fn area(scene: &ObjScene<f64, f32>, triangle: &MeshTriangle) -> f64 {
+        let [p0, p1, p2] = triangle.positions.map(|i| scene.positions[i]);
+        (p1 - p0).cross(p2 - p0).length() * 0.5
+    }
+
+    #[test]
+    fn polygons_are_triangulated() {
+        let scene = parse(&format!("{SQUARE}f 1 2 3 4\n")).unwrap();
+        let triangles = &scene.groups[0].triangles;
+        assert_eq!(triangles.len(), 2);
+        let total: f64 = triangles.iter().map(|t| area(&scene, t)).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+
+        // concave L shape in the yz plane, with an area of 3
+        let source = "v 0 0 0\nv 0 2 0\nv 0 2 1\nv 0 1 1\nv 0 1 2\nv 0 0 2\nf 1 2 3 4 5 6\n";
+        let scene = parse(source).unwrap();
+        let triangles = &scene.groups[0].triangles;
+        assert_eq!(triangles.len(), 4);
+        let total: f64 = triangles.iter().map(|t| area(&scene, t)).sum();
+        assert!((total - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn groups_split_faces() {
+        let source = format!("{SQUARE}f 1 2 3\ng first\nf 1 2 3\nf 1 3 4\no second one\nf 1 3 4\n");
+        let scene = parse(&source).unwrap();
+        let groups: Vec<(&str, usize)> = scene
+            .groups
+            .iter()
+            .map(|g| (g.name.as_str(), g.triangles.len()))
+            .collect();
+        assert_eq!(groups, [("", 1), ("first", 2), ("second one", 1)]);
+    }
+
+    #[test]
+    fn unknown_material_falls_back_to_the_default() {
+        let path = with_mtl("unknown", "newmtl red\nKd 1 0 0\n");
+        let source =
+            format!("mtllib test.mtl\n{SQUARE}usemtl red\nf 1 2 3\nusemtl blue\nf 1 3 4\n");
+        let scene = ObjScene::<f64, f32>::parse(&source, &path).unwrap();
+        assert_eq!(scene.groups.len(), 2);
+        assert_eq!(scene.groups[0].material, Some(0));
+        assert_eq!(scene.groups[1].material, None);
+        assert_eq!(
+            scene.warnings,
+            [format!(
+                "{}:8: unknown material 'blue', using the default",
+                path.display()
+            )]
+        );
+    }
+
+    #[test]
+    fn mtl_errors_report_the_mtl_line() {
+        let path = with_mtl("error", "newmtl red\nKd 1 0\n");
+        let error = ObjScene::<f64, f32>::parse("mtllib test.mtl\n", &path)
+            .err()
+            .unwrap()
+            .to_string();
+        let mtl = path.with_file_name("test.mtl");
+        assert_eq!(error, format!("{}:2: expected 3 numbers", mtl.display()));
+    }
+
+    /// Colour a material reflects for a ray hitting a z = 0 face head on
+    fn metal_albedo(material: &ObjMaterial<f32>) -> RGB<f32> {
+        assert!(matches!(material.kind, ObjMaterialKind::Metal(_)));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = HitRecord::new(
+            &ray,
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            material,
+            1.0,
+            0.0,
+            0.0,
+        );
+        let mut rng = RandomNumberGenerator::new(1);
+        let (_, attenuation) =
+            Material::<f64, f32>::scatter(material, &mut RNGAdapter(&mut rng), &ray, &hit).unwrap();
+        attenuation
+    }
+
+    #[test]
+    fn reflective_materials_use_kd_without_ks() {
+        let mtl = "newmtl diffuse_only\nKd 0.2 0.4 0.6\nNs 1000\nillum 3\n\
+                   newmtl both\nKd 0.2 0.4 0.6\nKs 0.9 0.8 0.7\nNs 1000\nillum 3\n";
+        let path = with_mtl("metal", mtl);
+        let scene = ObjScene::<f64, f32>::parse("mtllib test.mtl\n", &path).unwrap();
+        let diffuse_only = metal_albedo(&scene.materials[0]);
+        assert_eq!(
+            (diffuse_only.r, diffuse_only.g, diffuse_only.b),
+            (0.2, 0.4, 0.6)
+        );
+        let both = metal_albedo(&scene.materials[1]);
+        assert_eq!((both.r, both.g, both.b), (0.9, 0.8, 0.7));
+    }
+}
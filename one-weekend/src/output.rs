@@ -0,0 +1,509 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use external::stb::{self, WriteError};
+use math::{Float, RGB};
+
+use crate::DisplaySettings;
+
+/// File formats renders can be saved as
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    /// 8-bit sRGB, loses everything above 1
+    Png,
+    /// 8-bit sRGB, uncompressed
+    Bmp,
+    /// 8-bit sRGB, run-length encoded
+    Tga,
+    /// 8-bit sRGB, lossy, `quality` goes from 1 to 100
+    Jpg { quality: u8 },
+    /// Radiance RGBE, shared exponent with 8-bit mantissas
+    Hdr,
+    /// Portable float map, raw 32-bit floats
+    Pfm,
+    /// OpenEXR, uncompressed 32-bit float scanlines
+    Exr,
+}
+
+impl ImageFormat {
+    pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+    /// Names accepted by [`ImageFormat::from_name`], which are also the extensions
+    /// [`ImageFormat::from_path`] recognizes
+    pub const NAMES: [&'static str; 7] = ["png", "bmp", "tga", "jpg", "hdr", "pfm", "exr"];
+
+    /// Format named by the extension of the path, ignoring case
+    pub fn from_path(path: &str) -> Option<ImageFormat> {
+        let extension = Path::new(path).extension()?.to_str()?;
+        ImageFormat::from_name(extension)
+    }
+
+    /// Format from its name or usual extension, ignoring case. JPEGs get the default
+    /// quality.
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(ImageFormat::Png),
+            "bmp" => Some(ImageFormat::Bmp),
+            "tga" => Some(ImageFormat::Tga),
+            "jpg" | "jpeg" => Some(ImageFormat::Jpg {
+                quality: Self::DEFAULT_JPEG_QUALITY,
+            }),
+            "hdr" => Some(ImageFormat::Hdr),
+            "pfm" => Some(ImageFormat::Pfm),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Tga => "tga",
+            ImageFormat::Jpg { .. } => "jpg",
+            ImageFormat::Hdr => "hdr",
+            ImageFormat::Pfm => "pfm",
+            ImageFormat::Exr => "exr",
+        }
+    }
+
+    /// Fails the way [`encode_image`] would when this build cannot write the format, so
+    /// it can be checked before rendering
+    pub fn check_writable(&self) -> Result<(), WriteError> {
+        let name = match self {
+            ImageFormat::Bmp => "BMP",
+            ImageFormat::Tga => "TGA",
+            ImageFormat::Jpg { .. } => "JPEG",
+            ImageFormat::Hdr => "Radiance HDR",
+            ImageFormat::Png | ImageFormat::Pfm | ImageFormat::Exr => return Ok(()),
+        };
+        if stb::WRITES_ALL_FORMATS {
+            Ok(())
+        } else {
+            Err(WriteError::Unsupported(name))
+        }
+    }
+
+    /// Whether the format stores 8-bit sRGB and goes through the display settings
+    pub fn is_low_dynamic_range(&self) -> bool {
+        matches!(
+            self,
+            ImageFormat::Png | ImageFormat::Bmp | ImageFormat::Tga | ImageFormat::Jpg { .. }
+        )
+    }
+}
+
+/// Why an image could not be written
+#[derive(Debug)]
+pub enum OutputError {
+    /// The extension of the path is not one of the supported formats
+    UnknownFormat(String),
+    Write {
+        path: String,
+        error: WriteError,
+    },
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputError::UnknownFormat(path) => write!(
+                f,
+                "{path}: unknown image format, expected .png, .bmp, .tga, .jpg, .hdr, .pfm or .exr"
+            ),
+            OutputError::Write { path, error } => write!(f, "{path}: {error}"),
+        }
+    }
+}
+
+impl Error for OutputError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            OutputError::Write { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Writes linear pixels, stored row by row from the top, in the format picked by the
+/// extension of the path, see [`encode_image`].
+pub fn write_image<U>(
+    path: &str,
+    width: u32,
+    height: u32,
+    pixels: &[RGB<U>],
+    display: &DisplaySettings,
+) -> Result<(), OutputError>
+where
+    U: Float,
+{
+    let format =
+        ImageFormat::from_path(path).ok_or_else(|| OutputError::UnknownFormat(path.to_string()))?;
+    write_image_as(path, format, width, height, pixels, display)
+}
+
+/// Writes linear pixels, stored row by row from the top, in the given format whatever the
+/// extension of the path is, see [`encode_image`].
+pub fn write_image_as<U>(
+    path: &str,
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    pixels: &[RGB<U>],
+    display: &DisplaySettings,
+) -> Result<(), OutputError>
+where
+    U: Float,
+{
+    let write = || {
+        let mut writer = BufWriter::new(File::create(path)?);
+        encode_image(&mut writer, format, width, height, pixels, display)?;
+        writer.flush()?;
+        Ok(())
+    };
+    write().map_err(|error| OutputError::Write {
+        path: path.to_string(),
+        error,
+    })
+}
+
+/// Streams linear pixels, stored row by row from the top, to the writer. Only the 8-bit
+/// formats go through the display settings, the float formats keep the full range of the
+/// render.
+pub fn encode_image<U, W>(
+    writer: &mut W,
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    pixels: &[RGB<U>],
+    display: &DisplaySettings,
+) -> Result<(), WriteError>
+where
+    U: Float,
+    W: Write,
+{
+    check_pixel_count(width, height, pixels.len())?;
+
+    let ldr = || display.to_srgb_array(pixels, width);
+    match format {
+        ImageFormat::Png => stb::write_png_to(writer, width, height, 3, &ldr()),
+        ImageFormat::Bmp => stb::write_bmp_to(writer, width, height, 3, &ldr()),
+        ImageFormat::Tga => stb::write_tga_to(writer, width, height, 3, &ldr()),
+        ImageFormat::Jpg { quality } => {
+            stb::write_jpg_to(writer, width, height, 3, &ldr(), quality)
+        }
+        ImageFormat::Hdr => stb::write_hdr_to(writer, width, height, 3, &to_f32_array(pixels)),
+        ImageFormat::Pfm => write_pfm(writer, width, height, pixels),
+        ImageFormat::Exr => write_exr(writer, width, height, pixels),
+    }
+}
+
+/// Same check as the stb writers do on their buffers, counting 3 values per pixel
+fn check_pixel_count(width: u32, height: u32, len: usize) -> Result<(), WriteError> {
+    let expected = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or(WriteError::DimensionOverflow {
+            width,
+            height,
+            components: 3,
+        })?;
+    if len * 3 != expected {
+        return Err(WriteError::SizeMismatch {
+            expected,
+            actual: len * 3,
+        });
+    }
+    Ok(())
+}
+
+fn to_f32_array<U: Float>(pixels: &[RGB<U>]) -> Vec<f32> {
+    pixels
+        .iter()
+        .flat_map(|p| [p.r, p.g, p.b].map(|c| c.to_f32().unwrap()))
+        .collect()
+}
+
+/// Writes a colour portable float map.
+///
+/// PFM stores rows from the bottom, the negative scale marks the floats as little
+/// endian.
+pub fn write_pfm<U: Float, W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    pixels: &[RGB<U>],
+) -> Result<(), WriteError> {
+    check_pixel_count(width, height, pixels.len())?;
+
+    write!(writer, "PF\n{width} {height}\n-1.0\n")?;
+    if width == 0 {
+        return Ok(());
+    }
+    for row in pixels.chunks_exact(width as usize).rev() {
+        for c in to_f32_array(row) {
+            writer.write_all(&c.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a single part, uncompressed, scanline OpenEXR image with 32-bit float R, G and
+/// B channels, the smallest file every EXR reader understands.
+pub fn write_exr<U: Float, W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    pixels: &[RGB<U>],
+) -> Result<(), WriteError> {
+    check_pixel_count(width, height, pixels.len())?;
+    if width == 0 || height == 0 {
+        return Err(WriteError::EmptyImage);
+    }
+    // coordinates and the size of a scanline are stored as ints
+    if width > i32::MAX as u32 / 12 || height > i32::MAX as u32 {
+        return Err(WriteError::DimensionOverflow {
+            width,
+            height,
+            components: 3,
+        });
+    }
+
+    const FLOAT: i32 = 2;
+    const NO_COMPRESSION: u8 = 0;
+    const INCREASING_Y: u8 = 0;
+
+    let (max_x, max_y) = (width as i32 - 1, height as i32 - 1);
+
+    let mut header = Vec::new();
+    // magic number, then version 2 with no flags: single part scanline file
+    header.extend_from_slice(&20000630i32.to_le_bytes());
+    header.extend_from_slice(&2i32.to_le_bytes());
+
+    // channels are listed, and stored in each scanline, in alphabetical order
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&FLOAT.to_le_bytes());
+        // pLinear and reserved bytes, then x and y sampling
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    exr_attribute(&mut header, "channels", "chlist", &channels);
+
+    exr_attribute(&mut header, "compression", "compression", &[NO_COMPRESSION]);
+    let window: Vec<u8> = [0, 0, max_x, max_y]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    exr_attribute(&mut header, "dataWindow", "box2i", &window);
+    exr_attribute(&mut header, "displayWindow", "box2i", &window);
+    exr_attribute(&mut header, "lineOrder", "lineOrder", &[INCREASING_Y]);
+    exr_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    exr_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    header.push(0);
+    writer.write_all(&header)?;
+
+    // offset table, one uncompressed scanline per block
+    let line_size = width as u64 * 3 * 4;
+    let first_block = header.len() as u64 + height as u64 * 8;
+    for y in 0..height as u64 {
+        let offset = first_block + y * (8 + line_size);
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+
+    for (y, row) in pixels.chunks_exact(width as usize).enumerate() {
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(line_size as i32).to_le_bytes())?;
+        for channel in [2, 1, 0] {
+            for pixel in row {
+                let c = pixel[channel].to_f32().unwrap();
+                writer.write_all(&c.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f32_at(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn i32_at(bytes: &[u8], offset: usize) -> i32 {
+        i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn two_by_one() -> Vec<RGB<f32>> {
+        vec![RGB::new(0.25, 0.5, 1.0), RGB::new(2.0, 4.0, -8.0)]
+    }
+
+    #[test]
+    fn pfm_header_and_pixels() {
+        let mut file = Vec::new();
+        write_pfm(&mut file, 2, 1, &two_by_one()).unwrap();
+        let header = b"PF\n2 1\n-1.0\n";
+        assert_eq!(&file[..header.len()], header);
+        let floats: Vec<f32> = (header.len()..file.len())
+            .step_by(4)
+            .map(|offset| f32_at(&file, offset))
+            .collect();
+        assert_eq!(floats, [0.25, 0.5, 1.0, 2.0, 4.0, -8.0]);
+    }
+
+    #[test]
+    fn pfm_rows_go_bottom_up() {
+        let pixels = [0.0, 1.0, 2.0, 3.0].map(|v| RGB::new(v, v, v));
+        let mut file = Vec::new();
+        write_pfm(&mut file, 2, 2, &pixels).unwrap();
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&file[..header.len()], header);
+        let reds: Vec<f32> = (header.len()..file.len())
+            .step_by(12)
+            .map(|offset| f32_at(&file, offset))
+            .collect();
+        assert_eq!(reds, [2.0, 3.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn pfm_checks_the_pixel_count() {
+        let mut file = Vec::new();
+        assert!(matches!(
+            write_pfm(&mut file, 3, 1, &two_by_one()),
+            Err(WriteError::SizeMismatch { .. })
+        ));
+    }
+
+    /// Splits the header into (name, type, value) attributes, returns them and the
+    /// offset just past the header
+    fn exr_attributes(file: &[u8]) -> (Vec<(String, String, Vec<u8>)>, usize) {
+        let mut offset = 8;
+        let mut attributes = Vec::new();
+        let read_string = |offset: &mut usize| {
+            let end = *offset + file[*offset..].iter().position(|&b| b == 0).unwrap();
+            let text = String::from_utf8(file[*offset..end].to_vec()).unwrap();
+            *offset = end + 1;
+            text
+        };
+        loop {
+            let name = read_string(&mut offset);
+            if name.is_empty() {
+                return (attributes, offset);
+            }
+            let kind = read_string(&mut offset);
+            let size = i32_at(file, offset) as usize;
+            offset += 4;
+            attributes.push((name, kind, file[offset..offset + size].to_vec()));
+            offset += size;
+        }
+    }
+
+    #[test]
+    fn exr_layout() {
+        let mut file = Vec::new();
+        write_exr(&mut file, 2, 1, &two_by_one()).unwrap();
+
+        assert_eq!(i32_at(&file, 0), 20000630);
+        assert_eq!(i32_at(&file, 4), 2);
+
+        let (attributes, header_end) = exr_attributes(&file);
+        let find = |name: &str| {
+            attributes
+                .iter()
+                .find(|(n, _, _)| n == name)
+                .map(|(_, kind, value)| (kind.as_str(), value.as_slice()))
+                .unwrap_or_else(|| panic!("missing attribute {name}"))
+        };
+        let mut channels = Vec::new();
+        for name in [b'B', b'G', b'R'] {
+            channels.extend_from_slice(&[name, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        }
+        channels.push(0);
+        let window: Vec<u8> = [0i32, 0, 1, 0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        assert_eq!(find("channels"), ("chlist", channels.as_slice()));
+        assert_eq!(find("compression"), ("compression", &[0u8][..]));
+        assert_eq!(find("dataWindow"), ("box2i", window.as_slice()));
+        assert_eq!(find("displayWindow"), ("box2i", window.as_slice()));
+        assert_eq!(find("lineOrder"), ("lineOrder", &[0u8][..]));
+        assert_eq!(
+            find("pixelAspectRatio"),
+            ("float", &1.0f32.to_le_bytes()[..])
+        );
+        assert_eq!(find("screenWindowCenter"), ("v2f", &[0u8; 8][..]));
+        assert_eq!(
+            find("screenWindowWidth"),
+            ("float", &1.0f32.to_le_bytes()[..])
+        );
+
+        // one offset per scanline, pointing right after the table
+        let offset = u64::from_le_bytes(file[header_end..header_end + 8].try_into().unwrap());
+        let scanline = header_end + 8;
+        assert_eq!(offset, scanline as u64);
+
+        // y, byte count, then each channel for the whole line in B, G, R order
+        assert_eq!(i32_at(&file, scanline), 0);
+        assert_eq!(i32_at(&file, scanline + 4), 24);
+        let floats: Vec<f32> = (0..6)
+            .map(|i| f32_at(&file, scanline + 8 + 4 * i))
+            .collect();
+        assert_eq!(floats, [1.0, -8.0, 0.5, 4.0, 0.25, 2.0]);
+        assert_eq!(file.len(), scanline + 8 + 24);
+    }
+
+    #[test]
+    fn exr_scanlines_go_top_down() {
+        let pixels = [0.0, 1.0, 2.0, 3.0].map(|v| RGB::new(v, v, v));
+        let mut file = Vec::new();
+        write_exr(&mut file, 2, 2, &pixels).unwrap();
+        let (_, header_end) = exr_attributes(&file);
+        for y in 0..2 {
+            let entry = header_end + 8 * y;
+            let offset = u64::from_le_bytes(file[entry..entry + 8].try_into().unwrap()) as usize;
+            assert_eq!(offset, header_end + 16 + y * 32);
+            assert_eq!(i32_at(&file, offset), y as i32);
+            assert_eq!(f32_at(&file, offset + 8), 2.0 * y as f32);
+            assert_eq!(f32_at(&file, offset + 12), 2.0 * y as f32 + 1.0);
+        }
+    }
+
+    #[test]
+    fn exr_rejects_empty_images() {
+        let mut file = Vec::new();
+        assert!(matches!(
+            write_exr::<f32, _>(&mut file, 0, 0, &[]),
+            Err(WriteError::EmptyImage)
+        ));
+    }
+}
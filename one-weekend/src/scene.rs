@@ -0,0 +1,59 @@
+use math::{Aabb, Float, Interval, Ray};
+
+use crate::{Background, Bvh, GeometricPrimitive, HitRecord, Hittable};
+
+/// World and background of a render.
+///
+/// Primitives hold their materials through `Arc`s and materials own their textures, so a
+/// scene owns everything it shows: it can be built in a function, returned, loaded from a
+/// file and shared between threads.
+pub struct Scene<T, U> {
+    world: Bvh<T, GeometricPrimitive<T, U>>,
+    background: Box<dyn Background<T, U>>,
+}
+
+impl<T, U> Scene<T, U>
+where
+    T: Float,
+    U: Float,
+    GeometricPrimitive<T, U>: Hittable<T, U>,
+{
+    pub fn new<B>(primitives: Vec<GeometricPrimitive<T, U>>, background: B) -> Scene<T, U>
+    where
+        B: Background<T, U> + 'static,
+    {
+        Scene::with_boxed_background(primitives, Box::new(background))
+    }
+
+    pub fn with_boxed_background(
+        primitives: Vec<GeometricPrimitive<T, U>>,
+        background: Box<dyn Background<T, U>>,
+    ) -> Scene<T, U> {
+        Scene {
+            world: Bvh::new(primitives),
+            background,
+        }
+    }
+
+    pub fn primitives(&self) -> &[GeometricPrimitive<T, U>] {
+        self.world.primitives()
+    }
+
+    pub fn background(&self) -> &dyn Background<T, U> {
+        self.background.as_ref()
+    }
+}
+
+impl<T, U> Hittable<T, U> for Scene<T, U>
+where
+    T: Float,
+    GeometricPrimitive<T, U>: Hittable<T, U>,
+{
+    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<'_, T, U>> {
+        self.world.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb<T> {
+        self.world.bounding_box()
+    }
+}
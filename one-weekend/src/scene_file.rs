@@ -0,0 +1,552 @@
+//! Text scene descriptions
+//!
+//! One statement per line, `#` starts a comment. Names are single words and have to be
+//! defined before they are used. Colours are three linear floats, where a texture is
+//! accepted the name of a texture can be given instead. Image and mesh paths are relative
+//! to the scene file and cannot contain spaces.
+//!
+//! ```text
+//! # render settings, the command line overrides them
+//! width 800
+//! height 450
+//! samples 100
+//! max_depth 50
+//!
+//! # camera
+//! lookfrom 13 2 3
+//! lookat 0 0 0
+//! up 0 1 0
+//! vfov 20
+//! defocus_angle 0.6
+//! focus_dist 10
+//! projection perspective                 # orthographic, fisheye or equirectangular
+//!
+//! # color R G B, gradient BOTTOM TOP, sky, environment PATH or daylight SUN_DIRECTION
+//! background sky
+//!
+//! # solid COLOR, checker SCALE EVEN ODD, image PATH, marble SCALE [SEED], wood RINGS [SEED]
+//! texture checks checker 0.32 0.2 0.3 0.1 0.9 0.9 0.9
+//!
+//! # lambertian ALBEDO, metal ALBEDO FUZZ, dielectric IOR or light EMIT
+//! material ground lambertian checks
+//! material glass dielectric 1.5
+//! material gold metal 0.8 0.6 0.2 0.1
+//! material lamp light 4 4 4
+//!
+//! sphere 0 -1000 0 1000 ground           # CENTER RADIUS MATERIAL
+//! sphere 0 1 0 1 glass
+//! quad -1 3 -1 2 0 0 0 0 2 lamp          # CORNER U V MATERIAL
+//! box 3 0 -1 5 2 1 gold                  # MIN MAX MATERIAL
+//! triangle -4 0 0 -3 2 0 -2 0 0 gold     # P0 P1 P2 MATERIAL
+//! mesh teapot.obj gold                   # PATH MATERIAL, for groups without a MTL material
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::SplitWhitespace;
+use std::sync::Arc;
+
+use math::{Degrees, Float, Point3, Vector3, RGB};
+use noise::{Perlin, Turbulence};
+use num::traits::AsPrimitive;
+
+use crate::{
+    box_sides, Background, CameraSettings, CheckerTexture, Dielectric, DiffuseLight,
+    EnvironmentMap, GeometricPrimitive, GradientBackground, Hittable, Image, ImageTexture,
+    Lambertian, MarbleTexture, Material, Metal, ObjScene, ProceduralSky, Projection, Quad, Scene,
+    SolidBackground, SolidColor, Sphere, Texture, Triangle, WoodTexture,
+};
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            SceneError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io { error, .. } => Some(error),
+            SceneError::Parse { .. } => None,
+        }
+    }
+}
+
+/// Image settings of a scene, anything left out is up to the caller
+#[derive(Clone, Copy, Default)]
+pub struct RenderSettings {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub samples_per_pixel: Option<u32>,
+    pub max_depth: Option<u32>,
+}
+
+type SharedTexture<T, U> = Arc<dyn Texture<T, U>>;
+
+pub struct SceneFile<T, U> {
+    pub camera: CameraSettings<T>,
+    pub render: RenderSettings,
+    /// Has a sky gradient background when the file does not set one
+    pub scene: Scene<T, U>,
+    /// Problems in the meshes that did not stop the load, as `path:line: message`
+    pub warnings: Vec<String>,
+}
+
+impl<T, U> SceneFile<T, U>
+where
+    T: Float + AsPrimitive<U>,
+    U: 'static + Float + Into<T>,
+    GeometricPrimitive<T, U>: Hittable<T, U>,
+{
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneFile<T, U>, SceneError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|error| SceneError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Self::parse(&source, path)
+    }
+
+    /// Parses the contents of a scene file, `path` is used to find images and meshes and
+    /// in errors
+    pub fn parse(source: &str, path: &Path) -> Result<SceneFile<T, U>, SceneError> {
+        let mut camera = CameraSettings::default();
+        let mut render = RenderSettings::default();
+        let mut background: Box<dyn Background<T, U>> = Box::new(GradientBackground::sky());
+        let mut primitives = Vec::new();
+        let mut warnings = Vec::new();
+        let folder = path.parent().unwrap_or(Path::new(""));
+        let mut textures: HashMap<String, SharedTexture<T, U>> = HashMap::new();
+        let mut materials: HashMap<String, Arc<dyn Material<T, U>>> = HashMap::new();
+
+        for (line_index, line) in source.lines().enumerate() {
+            let mut parser = LineParser::new(line, line_index + 1, path);
+            let Some(keyword) = parser.next() else {
+                continue;
+            };
+            match keyword {
+                "width" => render.width = Some(parser.positive_integer()?),
+                "height" => render.height = Some(parser.positive_integer()?),
+                "samples" => render.samples_per_pixel = Some(parser.positive_integer()?),
+                "max_depth" => render.max_depth = Some(parser.integer()?),
+
+                "lookfrom" => camera.lookfrom = parser.point()?,
+                "lookat" => camera.lookat = parser.point()?,
+                "up" => camera.up = parser.vector()?,
+                "vfov" => camera.vfov = Degrees(parser.float()?),
+                "defocus_angle" => camera.defocus_angle = Degrees(parser.float()?),
+                "focus_dist" => camera.focus_dist = parser.float()?,
+                "projection" => {
+                    let name = parser.word("a projection")?;
+                    camera.projection = Projection::from_name(name)
+                        .ok_or_else(|| parser.error(format!("unknown projection '{}'", name)))?;
+                }
+
+                "background" => {
+                    background = match parser.word("a background kind")? {
+                        "color" => Box::new(SolidBackground::new(parser.color()?)),
+                        "gradient" => {
+                            Box::new(GradientBackground::new(parser.color()?, parser.color()?))
+                        }
+                        "sky" => Box::new(GradientBackground::sky()),
+                        "environment" => Box::new(EnvironmentMap::new(parser.image(folder)?)),
+                        "daylight" => Box::new(ProceduralSky::new(parser.vector()?)),
+                        kind => return Err(parser.error(format!("unknown background '{}'", kind))),
+                    };
+                }
+                "texture" => {
+                    let name = parser.new_name(&textures, "texture")?;
+                    let texture: SharedTexture<T, U> = match parser.word("a texture kind")? {
+                        "solid" => Arc::new(SolidColor::new(parser.color()?)),
+                        "checker" => Arc::new(CheckerTexture::new(
+                            parser.float()?,
+                            parser.texture(&textures)?,
+                            parser.texture(&textures)?,
+                        )),
+                        "image" => Arc::new(ImageTexture::new(parser.image(folder)?)),
+                        "marble" => {
+                            let scale = parser.float()?;
+                            let noise = Turbulence::new(Perlin::new(parser.seed()?), 7);
+                            Arc::new(MarbleTexture::new(noise, scale))
+                        }
+                        "wood" => {
+                            let rings = parser.float()?;
+                            Arc::new(WoodTexture::new(Perlin::new(parser.seed()?), rings))
+                        }
+                        kind => return Err(parser.error(format!("unknown texture '{}'", kind))),
+                    };
+                    textures.insert(name, texture);
+                }
+                "material" => {
+                    let name = parser.new_name(&materials, "material")?;
+                    let material: Arc<dyn Material<T, U>> = match parser.word("a material kind")? {
+                        "lambertian" => {
+                            Arc::new(Lambertian::from_texture(parser.texture(&textures)?))
+                        }
+                        "metal" => Arc::new(Metal::from_texture(
+                            parser.texture(&textures)?,
+                            parser.float()?,
+                        )),
+                        "dielectric" => Arc::new(Dielectric::new(parser.float()?)),
+                        "light" => Arc::new(DiffuseLight::from_texture(parser.texture(&textures)?)),
+                        kind => {
+                            return Err(parser.error(format!("unknown material kind '{}'", kind)))
+                        }
+                    };
+                    materials.insert(name, material);
+                }
+
+                "sphere" => primitives.push(GeometricPrimitive::Sphere(Sphere::new(
+                    parser.point()?,
+                    parser.float()?,
+                    parser.material(&materials)?,
+                ))),
+                "quad" => primitives.push(GeometricPrimitive::Quad(Quad::new(
+                    parser.point()?,
+                    parser.vector()?,
+                    parser.vector()?,
+                    parser.material(&materials)?,
+                ))),
+                "box" => {
+                    let (a, b) = (parser.point()?, parser.point()?);
+                    let material = parser.material(&materials)?;
+                    for (corner, u, v) in box_sides(a, b) {
+                        primitives.push(GeometricPrimitive::Quad(Quad::new(
+                            corner,
+                            u,
+                            v,
+                            material.clone(),
+                        )));
+                    }
+                }
+                "triangle" => primitives.push(GeometricPrimitive::Triangle(Triangle::new(
+                    parser.point()?,
+                    parser.point()?,
+                    parser.point()?,
+                    parser.material(&materials)?,
+                ))),
+                "mesh" => {
+                    let file = folder.join(parser.word("a path")?);
+                    let mesh = ObjScene::load(&file)
+                        .map_err(|error| parser.error(format!("cannot load mesh: {}", error)))?;
+                    let material = parser.material(&materials)?;
+                    warnings.extend(mesh.warnings.iter().cloned());
+                    primitives.extend(
+                        mesh.meshes(material)
+                            .into_iter()
+                            .map(GeometricPrimitive::Mesh),
+                    );
+                }
+                _ => return Err(parser.error(format!("unknown statement '{}'", keyword))),
+            }
+            parser.finish()?;
+        }
+
+        Ok(SceneFile {
+            camera,
+            render,
+            scene: Scene::with_boxed_background(primitives, background),
+            warnings,
+        })
+    }
+}
+
+struct LineParser<'a> {
+    tokens: Peekable<SplitWhitespace<'a>>,
+    line_number: usize,
+    path: &'a Path,
+}
+
+impl<'a> LineParser<'a> {
+    fn new(line: &'a str, line_number: usize, path: &'a Path) -> LineParser<'a> {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        LineParser {
+            tokens: line.split_whitespace().peekable(),
+            line_number,
+            path,
+        }
+    }
+
+    fn error(&self, message: String) -> SceneError {
+        SceneError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line_number,
+            message,
+        }
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.tokens.next()
+    }
+
+    /// Statements take a fixed number of values, anything left is a mistake
+    fn finish(&mut self) -> Result<(), SceneError> {
+        match self.tokens.next() {
+            Some(token) => Err(self.error(format!("unexpected '{}'", token))),
+            None => Ok(()),
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<&'a str, SceneError> {
+        self.tokens
+            .next()
+            .ok_or_else(|| self.error(format!("expected {}", what)))
+    }
+
+    fn float<F: Float>(&mut self) -> Result<F, SceneError> {
+        let token = self.word("a number")?;
+        token
+            .parse::<f64>()
+            .ok()
+            .and_then(F::from_f64)
+            .ok_or_else(|| self.error(format!("invalid number '{}'", token)))
+    }
+
+    fn floats<F: Float, const N: usize>(&mut self) -> Result<[F; N], SceneError> {
+        let mut values = [F::constant(0.0); N];
+        for value in values.iter_mut() {
+            *value = self.float()?;
+        }
+        Ok(values)
+    }
+
+    fn point<T: Float>(&mut self) -> Result<Point3<T>, SceneError> {
+        let [x, y, z] = self.floats()?;
+        Ok(Point3::new(x, y, z))
+    }
+
+    fn vector<T: Float>(&mut self) -> Result<Vector3<T>, SceneError> {
+        let [x, y, z] = self.floats()?;
+        Ok(Vector3::new(x, y, z))
+    }
+
+    fn color<U: Float>(&mut self) -> Result<RGB<U>, SceneError> {
+        let [r, g, b] = self.floats()?;
+        Ok(RGB::new(r, g, b))
+    }
+
+    fn integer(&mut self) -> Result<u32, SceneError> {
+        let token = self.word("an integer")?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("invalid integer '{}'", token)))
+    }
+
+    fn positive_integer(&mut self) -> Result<u32, SceneError> {
+        match self.integer()? {
+            0 => Err(self.error("expected a value of at least 1".to_string())),
+            value => Ok(value),
+        }
+    }
+
+    /// Optional noise seed, 0 when left out
+    fn seed(&mut self) -> Result<u32, SceneError> {
+        match self.tokens.peek() {
+            Some(_) => self.integer(),
+            None => Ok(0),
+        }
+    }
+
+    /// Name for a new texture or material, which must not be taken yet
+    fn new_name<V>(
+        &mut self,
+        defined: &HashMap<String, V>,
+        what: &str,
+    ) -> Result<String, SceneError> {
+        let name = self.word(&format!("a {} name", what))?;
+        if defined.contains_key(name) {
+            return Err(self.error(format!("{} '{}' is already defined", what, name)));
+        }
+        Ok(name.to_string())
+    }
+
+    fn material<T, U>(
+        &mut self,
+        materials: &HashMap<String, Arc<dyn Material<T, U>>>,
+    ) -> Result<Arc<dyn Material<T, U>>, SceneError> {
+        let name = self.word("a material name")?;
+        materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| self.error(format!("unknown material '{}'", name)))
+    }
+
+    /// Either a colour or the name of a texture
+    fn texture<T, U>(
+        &mut self,
+        textures: &HashMap<String, SharedTexture<T, U>>,
+    ) -> Result<SharedTexture<T, U>, SceneError>
+    where
+        U: 'static + Float,
+    {
+        let starts_with_number = self
+            .tokens
+            .peek()
+            .is_some_and(|token| token.parse::<f64>().is_ok());
+        if starts_with_number {
+            return Ok(Arc::new(SolidColor::new(self.color()?)));
+        }
+        let name = self.word("a colour or a texture name")?;
+        textures
+            .get(name)
+            .cloned()
+            .ok_or_else(|| self.error(format!("unknown texture '{}'", name)))
+    }
+
+    fn image<U: Float>(&mut self, folder: &Path) -> Result<Image<U>, SceneError> {
+        let file = folder.join(self.word("a path")?);
+        Image::load(&file.to_string_lossy())
+            .map_err(|error| self.error(format!("cannot load image: {}", error)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<SceneFile<f64, f32>, SceneError> {
+        SceneFile::parse(source, Path::new("test.scene"))
+    }
+
+    fn parse_error(source: &str) -> String {
+        match parse(source) {
+            Ok(_) => panic!("expected an error"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_the_example() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/example.scene");
+        let file = SceneFile::<f64, f32>::load(&path).unwrap();
+        assert!(file.warnings.is_empty());
+
+        assert_eq!(file.render.width, Some(800));
+        assert_eq!(file.render.height, Some(450));
+        assert_eq!(file.render.samples_per_pixel, Some(200));
+        assert_eq!(file.render.max_depth, Some(50));
+
+        let camera = &file.camera;
+        assert_eq!(
+            [camera.lookfrom.x, camera.lookfrom.y, camera.lookfrom.z],
+            [3.0, 2.5, 13.0]
+        );
+        assert_eq!(
+            [camera.lookat.x, camera.lookat.y, camera.lookat.z],
+            [0.0, 1.0, 0.0]
+        );
+        assert_eq!(camera.vfov.0, 30.0);
+        assert_eq!(camera.defocus_angle.0, 0.6);
+        assert_eq!(camera.focus_dist, 13.4);
+        assert_eq!(camera.projection, Projection::Perspective);
+
+        // four spheres and the lamp
+        assert_eq!(file.scene.primitives().len(), 5);
+    }
+
+    #[test]
+    fn boxes_and_triangles_add_primitives() {
+        let source = "material m lambertian 0.5 0.5 0.5\n\
+                      box 0 0 0 1 1 1 m\n\
+                      triangle 0 0 0 1 0 0 0 1 0 m\n";
+        let file = parse(source).unwrap();
+        assert_eq!(file.scene.primitives().len(), 7);
+    }
+
+    #[test]
+    fn errors_report_their_line() {
+        let cases = [
+            ("sphres 0 0 0 1 m\n", 1, "unknown statement 'sphres'"),
+            (
+                "\n\n# comment\nfrobnicate\n",
+                4,
+                "unknown statement 'frobnicate'",
+            ),
+            ("width\n", 1, "expected an integer"),
+            ("width 0\n", 1, "expected a value of at least 1"),
+            ("width 12.5\n", 1, "invalid integer '12.5'"),
+            ("vfov\n", 1, "expected a number"),
+            ("lookfrom 1 2\n", 1, "expected a number"),
+            ("lookfrom 1 2 x\n", 1, "invalid number 'x'"),
+            ("vfov 20 30\n", 1, "unexpected '30'"),
+            ("\nsphere 0 0 0 1 ghost\n", 2, "unknown material 'ghost'"),
+            (
+                "material m lambertian 1 1 1\n\nsphere 0 0 0 1\n",
+                3,
+                "expected a material name",
+            ),
+            (
+                "material m lambertian 1 1 1\nmaterial m metal 1 1 1 0\n",
+                2,
+                "material 'm' is already defined",
+            ),
+            (
+                "material m lambertian stripes\n",
+                1,
+                "unknown texture 'stripes'",
+            ),
+            ("material m plastic\n", 1, "unknown material kind 'plastic'"),
+            ("texture t plaid\n", 1, "unknown texture 'plaid'"),
+            ("background nebula\n", 1, "unknown background 'nebula'"),
+            ("projection fisheye 2\n", 1, "unexpected '2'"),
+            ("projection pinhole\n", 1, "unknown projection 'pinhole'"),
+        ];
+        for (source, line, message) in cases {
+            assert_eq!(
+                parse_error(source),
+                format!("test.scene:{line}: {message}"),
+                "for {source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn missing_files_report_the_statement() {
+        let error = parse_error("material m lambertian 1 1 1\nmesh missing.obj m\n");
+        assert!(
+            error.starts_with("test.scene:2: cannot load mesh: missing.obj: "),
+            "{error}"
+        );
+        let error = parse_error("texture t image missing.png\n");
+        assert!(
+            error.starts_with("test.scene:1: cannot load image: missing.png: "),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn missing_scene_file_is_an_io_error() {
+        match SceneFile::<f64, f32>::load("does/not/exist.scene") {
+            Err(SceneError::Io { path, .. }) => assert_eq!(path, Path::new("does/not/exist.scene")),
+            _ => panic!("expected an io error"),
+        }
+    }
+}
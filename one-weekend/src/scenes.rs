@@ -0,0 +1,479 @@
+//! The scenes of the first two books of the Ray Tracing in One Weekend series, with the
+//! camera and image settings they are shown with.
+//!
+//! The Earth of the books is a photograph wrapped around a sphere, the scenes showing it
+//! take the path of an equirectangular image of the Earth. The renderer has no motion
+//! blur and no participating media, so the final scene of *The Next Week* is built
+//! without its moving sphere and fog.
+
+use std::sync::Arc;
+
+use external::stb::LoadError;
+use math::{Degrees, Point3, Radians, Vector3, RGB};
+use noise::{Perlin, RandomNumberGenerator, Turbulence};
+
+use crate::{
+    box_sides, CameraSettings, CheckerTexture, Dielectric, DiffuseLight, GeometricPrimitive,
+    GradientBackground, Image, ImageTexture, Lambertian, MarbleTexture, Material, Metal, Quad,
+    RNGAdapter, RenderSettings, Scene, SolidBackground, Sphere,
+};
+
+/// Names accepted by [`from_name`], in the order of the books
+pub const NAMES: [&str; 8] = [
+    "random-spheres",
+    "checkered-spheres",
+    "earth",
+    "perlin-spheres",
+    "quads",
+    "simple-light",
+    "cornell-box",
+    "next-week",
+];
+
+/// A scene with the camera and image settings it is meant to be seen with
+pub struct BuiltinScene {
+    pub scene: Scene<f64, f32>,
+    pub camera: CameraSettings<f64>,
+    pub render: RenderSettings,
+}
+
+/// Names of the scenes that load the image of the Earth
+pub const NEEDS_EARTH_MAP: [&str; 2] = ["earth", "next-week"];
+
+/// Builds the scene called `name`, `seed` drives its random placement and noise and
+/// `earth_map` is the image of the Earth for the scenes that show it.
+///
+/// None when there is no scene with that name, an error when the image cannot be loaded.
+pub fn from_name(
+    name: &str,
+    seed: u32,
+    earth_map: &str,
+) -> Option<Result<BuiltinScene, LoadError>> {
+    Some(Ok(match name {
+        "random-spheres" => random_spheres(seed),
+        "checkered-spheres" => checkered_spheres(),
+        "earth" => return Some(earth(earth_map)),
+        "perlin-spheres" => perlin_spheres(seed),
+        "quads" => quads(),
+        "simple-light" => simple_light(seed),
+        "cornell-box" => cornell_box(),
+        "next-week" => return Some(next_week(seed, earth_map)),
+        _ => return None,
+    }))
+}
+
+type SharedMaterial = Arc<dyn Material<f64, f32>>;
+
+fn sphere(
+    center: Point3<f64>,
+    radius: f64,
+    material: SharedMaterial,
+) -> GeometricPrimitive<f64, f32> {
+    GeometricPrimitive::Sphere(Sphere::new(center, radius, material))
+}
+
+fn quad(
+    corner: Point3<f64>,
+    u: Vector3<f64>,
+    v: Vector3<f64>,
+    material: SharedMaterial,
+) -> GeometricPrimitive<f64, f32> {
+    GeometricPrimitive::Quad(Quad::new(corner, u, v, material))
+}
+
+/// Rotation by `angle` around the Y axis, counterclockwise seen from above
+fn turn_y(angle: Degrees<f64>) -> impl Fn(Vector3<f64>) -> Vector3<f64> {
+    let Radians(angle) = angle.into();
+    let (sin, cos) = angle.sin_cos();
+    move |v| Vector3::new(cos * v.x + sin * v.z, v.y, cos * v.z - sin * v.x)
+}
+
+/// The box between the origin and `size`, turned around the Y axis and then moved by
+/// `offset`
+fn turned_box(
+    size: Vector3<f64>,
+    angle: Degrees<f64>,
+    offset: Vector3<f64>,
+    material: SharedMaterial,
+) -> impl Iterator<Item = GeometricPrimitive<f64, f32>> {
+    let turn = turn_y(angle);
+    let origin = Point3::new(0.0, 0.0, 0.0);
+    box_sides(origin, origin + size)
+        .into_iter()
+        .map(move |(corner, u, v)| {
+            let corner = origin + turn(corner - origin) + offset;
+            quad(corner, turn(u), turn(v), material.clone())
+        })
+}
+
+/// Lambertian with a marble texture, the "noise texture" of the books
+fn marble(scale: f64, seed: u32) -> SharedMaterial {
+    let noise = Turbulence::new(Perlin::new(seed), 7);
+    Arc::new(Lambertian::from_texture(MarbleTexture::new(noise, scale)))
+}
+
+/// Lambertian with the equirectangular image at `path` wrapped around spheres
+fn globe(path: &str) -> Result<SharedMaterial, LoadError> {
+    let texture = ImageTexture::new(Image::load(path)?);
+    Ok(Arc::new(Lambertian::from_texture(texture)))
+}
+
+/// Image settings of the books, `aspect_ratio` is width over height
+fn render(width: u32, aspect_ratio: f64, samples_per_pixel: u32, max_depth: u32) -> RenderSettings {
+    RenderSettings {
+        width: Some(width),
+        height: Some(((width as f64 / aspect_ratio).round() as u32).max(1)),
+        samples_per_pixel: Some(samples_per_pixel),
+        max_depth: Some(max_depth),
+    }
+}
+
+/// Camera looking at `lookat` from `lookfrom` with Y up and everything in focus
+fn camera(lookfrom: Point3<f64>, lookat: Point3<f64>, vfov: f64) -> CameraSettings<f64> {
+    CameraSettings {
+        lookfrom,
+        lookat,
+        up: Vector3::new(0.0, 1.0, 0.0),
+        vfov: Degrees(vfov),
+        ..CameraSettings::default()
+    }
+}
+
+/// Final scene of the first book, small random spheres around three large ones
+pub fn random_spheres(seed: u32) -> BuiltinScene {
+    let mut rng = RandomNumberGenerator::new(seed);
+
+    let material_ground = Arc::new(Lambertian::new(RGB::new(0.5, 0.5, 0.5)));
+    let material1: SharedMaterial = Arc::new(Dielectric::new(1.5));
+    let material2 = Arc::new(Lambertian::new(RGB::new(0.4, 0.2, 0.1)));
+    let material3 = Arc::new(Metal::new(RGB::new(0.7, 0.6, 0.5), 0.0));
+
+    let mut materials: Vec<SharedMaterial> = Vec::with_capacity(500);
+    for _ in 0..500 {
+        let choose_mat = rng.next_f32();
+        if choose_mat < 0.8 {
+            // lambertian
+            let albedo =
+                RGB::random(&mut RNGAdapter(&mut rng)) * RGB::random(&mut RNGAdapter(&mut rng));
+            materials.push(Arc::new(Lambertian::new(albedo)));
+        } else if choose_mat < 0.95 {
+            let albedo = RGB::random_range(&mut RNGAdapter(&mut rng), 0.5, 1.0);
+            let fuzz = rng.next_range_f32(0.0, 0.5);
+            materials.push(Arc::new(Metal::new(albedo, fuzz)));
+        } else {
+            materials.push(material1.clone());
+        }
+    }
+
+    let mut world = vec![
+        sphere(Point3::new(0.0, -1000.0, 0.0), 1000.0, material_ground),
+        sphere(Point3::new(0.0, 1.0, 0.0), 1.0, material1),
+        sphere(Point3::new(-4.0, 1.0, 0.0), 1.0, material2),
+        sphere(Point3::new(4.0, 1.0, 0.0), 1.0, material3),
+    ];
+
+    let mut materials = materials.into_iter();
+    for a in -11..11 {
+        for b in -11..11 {
+            let center = Point3::new(
+                a as f64 + rng.next_range_f64(0.0, 0.9),
+                0.2,
+                b as f64 + rng.next_range_f64(0.0, 0.9),
+            );
+            if (center - Point3::new(4.0, 0.2, 0.0)).length_squared() > 0.9 * 0.9 {
+                world.push(sphere(center, 0.2, materials.next().unwrap()));
+            }
+        }
+    }
+
+    BuiltinScene {
+        scene: Scene::new(world, GradientBackground::sky()),
+        camera: CameraSettings {
+            defocus_angle: Degrees(0.6),
+            focus_dist: 10.0,
+            ..camera(
+                Point3::new(13.0, 2.0, 3.0),
+                Point3::new(0.0, 0.0, 0.0),
+                20.0,
+            )
+        },
+        render: render(1200, 16.0 / 9.0, 500, 50),
+    }
+}
+
+/// Two large spheres touching at the origin, with a checker texture
+pub fn checkered_spheres() -> BuiltinScene {
+    let checker: SharedMaterial = Arc::new(Lambertian::from_texture(CheckerTexture::from_colors(
+        0.32,
+        RGB::new(0.2, 0.3, 0.1),
+        RGB::new(0.9, 0.9, 0.9),
+    )));
+
+    let world = vec![
+        sphere(Point3::new(0.0, -10.0, 0.0), 10.0, checker.clone()),
+        sphere(Point3::new(0.0, 10.0, 0.0), 10.0, checker),
+    ];
+
+    BuiltinScene {
+        scene: Scene::new(world, GradientBackground::sky()),
+        camera: camera(
+            Point3::new(13.0, 2.0, 3.0),
+            Point3::new(0.0, 0.0, 0.0),
+            20.0,
+        ),
+        render: render(400, 16.0 / 9.0, 100, 50),
+    }
+}
+
+/// The Earth alone in the sky, `earth_map` is the path of an equirectangular image of it
+pub fn earth(earth_map: &str) -> Result<BuiltinScene, LoadError> {
+    let world = vec![sphere(Point3::new(0.0, 0.0, 0.0), 2.0, globe(earth_map)?)];
+
+    Ok(BuiltinScene {
+        scene: Scene::new(world, GradientBackground::sky()),
+        camera: camera(
+            Point3::new(0.0, 0.0, 12.0),
+            Point3::new(0.0, 0.0, 0.0),
+            20.0,
+        ),
+        render: render(400, 16.0 / 9.0, 100, 50),
+    })
+}
+
+/// A marble sphere on a marble ground
+pub fn perlin_spheres(seed: u32) -> BuiltinScene {
+    let texture = marble(4.0, seed);
+    let world = vec![
+        sphere(Point3::new(0.0, -1000.0, 0.0), 1000.0, texture.clone()),
+        sphere(Point3::new(0.0, 2.0, 0.0), 2.0, texture),
+    ];
+
+    BuiltinScene {
+        scene: Scene::new(world, GradientBackground::sky()),
+        camera: camera(
+            Point3::new(13.0, 2.0, 3.0),
+            Point3::new(0.0, 0.0, 0.0),
+            20.0,
+        ),
+        render: render(400, 16.0 / 9.0, 100, 50),
+    }
+}
+
+/// Five coloured quads around the camera's line of sight
+pub fn quads() -> BuiltinScene {
+    let lambertian = |r, g, b| -> SharedMaterial { Arc::new(Lambertian::new(RGB::new(r, g, b))) };
+
+    let world = vec![
+        // left red
+        quad(
+            Point3::new(-3.0, -2.0, 5.0),
+            Vector3::new(0.0, 0.0, -4.0),
+            Vector3::new(0.0, 4.0, 0.0),
+            lambertian(1.0, 0.2, 0.2),
+        ),
+        // back green
+        quad(
+            Point3::new(-2.0, -2.0, 0.0),
+            Vector3::new(4.0, 0.0, 0.0),
+            Vector3::new(0.0, 4.0, 0.0),
+            lambertian(0.2, 1.0, 0.2),
+        ),
+        // right blue
+        quad(
+            Point3::new(3.0, -2.0, 1.0),
+            Vector3::new(0.0, 0.0, 4.0),
+            Vector3::new(0.0, 4.0, 0.0),
+            lambertian(0.2, 0.2, 1.0),
+        ),
+        // upper orange
+        quad(
+            Point3::new(-2.0, 3.0, 1.0),
+            Vector3::new(4.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 4.0),
+            lambertian(1.0, 0.5, 0.0),
+        ),
+        // lower teal
+        quad(
+            Point3::new(-2.0, -3.0, 5.0),
+            Vector3::new(4.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -4.0),
+            lambertian(0.2, 0.8, 0.8),
+        ),
+    ];
+
+    BuiltinScene {
+        scene: Scene::new(world, GradientBackground::sky()),
+        camera: camera(Point3::new(0.0, 0.0, 9.0), Point3::new(0.0, 0.0, 0.0), 80.0),
+        render: render(400, 1.0, 100, 50),
+    }
+}
+
+/// The marble spheres lit only by a rectangle and a sphere of light
+pub fn simple_light(seed: u32) -> BuiltinScene {
+    let texture = marble(4.0, seed);
+    let light: SharedMaterial = Arc::new(DiffuseLight::new(RGB::new(4.0, 4.0, 4.0)));
+
+    let world = vec![
+        sphere(Point3::new(0.0, -1000.0, 0.0), 1000.0, texture.clone()),
+        sphere(Point3::new(0.0, 2.0, 0.0), 2.0, texture),
+        quad(
+            Point3::new(3.0, 1.0, -2.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+            light.clone(),
+        ),
+        sphere(Point3::new(0.0, 7.0, 0.0), 2.0, light),
+    ];
+
+    BuiltinScene {
+        scene: Scene::new(world, SolidBackground::new(RGB::black())),
+        camera: camera(
+            Point3::new(26.0, 3.0, 6.0),
+            Point3::new(0.0, 2.0, 0.0),
+            20.0,
+        ),
+        render: render(400, 16.0 / 9.0, 100, 50),
+    }
+}
+
+/// Red and green walls, a ceiling light and two turned white boxes
+pub fn cornell_box() -> BuiltinScene {
+    let red: SharedMaterial = Arc::new(Lambertian::new(RGB::new(0.65, 0.05, 0.05)));
+    let white: SharedMaterial = Arc::new(Lambertian::new(RGB::new(0.73, 0.73, 0.73)));
+    let green: SharedMaterial = Arc::new(Lambertian::new(RGB::new(0.12, 0.45, 0.15)));
+    let light: SharedMaterial = Arc::new(DiffuseLight::new(RGB::new(15.0, 15.0, 15.0)));
+
+    let mut world = vec![
+        quad(
+            Point3::new(555.0, 0.0, 0.0),
+            Vector3::new(0.0, 555.0, 0.0),
+            Vector3::new(0.0, 0.0, 555.0),
+            green,
+        ),
+        quad(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 555.0, 0.0),
+            Vector3::new(0.0, 0.0, 555.0),
+            red,
+        ),
+        quad(
+            Point3::new(343.0, 554.0, 332.0),
+            Vector3::new(-130.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -105.0),
+            light,
+        ),
+        quad(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(555.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 555.0),
+            white.clone(),
+        ),
+        quad(
+            Point3::new(555.0, 555.0, 555.0),
+            Vector3::new(-555.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -555.0),
+            white.clone(),
+        ),
+        quad(
+            Point3::new(0.0, 0.0, 555.0),
+            Vector3::new(555.0, 0.0, 0.0),
+            Vector3::new(0.0, 555.0, 0.0),
+            white.clone(),
+        ),
+    ];
+    world.extend(turned_box(
+        Vector3::new(165.0, 330.0, 165.0),
+        Degrees(15.0),
+        Vector3::new(265.0, 0.0, 295.0),
+        white.clone(),
+    ));
+    world.extend(turned_box(
+        Vector3::new(165.0, 165.0, 165.0),
+        Degrees(-18.0),
+        Vector3::new(130.0, 0.0, 65.0),
+        white,
+    ));
+
+    BuiltinScene {
+        scene: Scene::new(world, SolidBackground::new(RGB::black())),
+        camera: camera(
+            Point3::new(278.0, 278.0, -800.0),
+            Point3::new(278.0, 278.0, 0.0),
+            40.0,
+        ),
+        render: render(600, 1.0, 200, 50),
+    }
+}
+
+/// Final scene of the second book: a floor of boxes, glass, metal, marble, the Earth and
+/// a turned cube of small spheres under a ceiling light. `earth_map` is the path of an
+/// equirectangular image of the Earth.
+pub fn next_week(seed: u32, earth_map: &str) -> Result<BuiltinScene, LoadError> {
+    let mut rng = RandomNumberGenerator::new(seed);
+    let mut world = Vec::new();
+
+    let ground: SharedMaterial = Arc::new(Lambertian::new(RGB::new(0.48, 0.83, 0.53)));
+    let boxes_per_side = 20;
+    for i in 0..boxes_per_side {
+        for j in 0..boxes_per_side {
+            let w = 100.0;
+            let x0 = -1000.0 + i as f64 * w;
+            let z0 = -1000.0 + j as f64 * w;
+            let y1 = rng.next_range_f64(1.0, 101.0);
+            world.extend(
+                box_sides(Point3::new(x0, 0.0, z0), Point3::new(x0 + w, y1, z0 + w))
+                    .into_iter()
+                    .map(|(corner, u, v)| quad(corner, u, v, ground.clone())),
+            );
+        }
+    }
+
+    let light = Arc::new(DiffuseLight::new(RGB::new(7.0, 7.0, 7.0)));
+    world.push(quad(
+        Point3::new(123.0, 554.0, 147.0),
+        Vector3::new(300.0, 0.0, 0.0),
+        Vector3::new(0.0, 0.0, 265.0),
+        light,
+    ));
+
+    // stands still, in the book it moves 30 units along X during the exposure
+    let orange = Arc::new(Lambertian::new(RGB::new(0.7, 0.3, 0.1)));
+    world.push(sphere(Point3::new(400.0, 400.0, 200.0), 50.0, orange));
+
+    let glass: SharedMaterial = Arc::new(Dielectric::new(1.5));
+    world.push(sphere(Point3::new(260.0, 150.0, 45.0), 50.0, glass.clone()));
+    let metal = Arc::new(Metal::new(RGB::new(0.8, 0.8, 0.9), 1.0));
+    world.push(sphere(Point3::new(0.0, 150.0, 145.0), 50.0, metal));
+    // the book fills this one with blue fog
+    world.push(sphere(Point3::new(360.0, 150.0, 145.0), 70.0, glass));
+
+    world.push(sphere(
+        Point3::new(400.0, 200.0, 400.0),
+        100.0,
+        globe(earth_map)?,
+    ));
+    world.push(sphere(
+        Point3::new(220.0, 280.0, 300.0),
+        80.0,
+        marble(0.2, seed),
+    ));
+
+    let white: SharedMaterial = Arc::new(Lambertian::new(RGB::new(0.73, 0.73, 0.73)));
+    let turn = turn_y(Degrees(15.0));
+    let offset = Vector3::new(-100.0, 270.0, 395.0);
+    for _ in 0..1000 {
+        let p = Vector3::random_range(&mut RNGAdapter(&mut rng), 0.0, 165.0);
+        let center = Point3::new(0.0, 0.0, 0.0) + turn(p) + offset;
+        world.push(sphere(center, 10.0, white.clone()));
+    }
+
+    Ok(BuiltinScene {
+        scene: Scene::new(world, SolidBackground::new(RGB::black())),
+        camera: camera(
+            Point3::new(478.0, 278.0, -600.0),
+            Point3::new(278.0, 278.0, 0.0),
+            40.0,
+        ),
+        render: render(800, 1.0, 10000, 40),
+    })
+}
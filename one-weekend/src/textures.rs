@@ -0,0 +1,317 @@
+use std::sync::Arc;
+
+use math::{Float, Point3, RGB};
+use noise::Noise;
+use num::traits::AsPrimitive;
+
+use crate::{Image, Wrap};
+
+/// Colour that varies over a surface, looked up with the texture coordinates and the
+/// position of the hit point
+pub trait Texture<T, U>: Send + Sync {
+    fn value(&self, u: T, v: T, point: Point3<T>) -> RGB<U>;
+}
+
+impl<T, U, X> Texture<T, U> for Box<X>
+where
+    X: Texture<T, U> + ?Sized,
+{
+    fn value(&self, u: T, v: T, point: Point3<T>) -> RGB<U> {
+        self.as_ref().value(u, v, point)
+    }
+}
+
+impl<T, U, X> Texture<T, U> for Arc<X>
+where
+    X: Texture<T, U> + ?Sized,
+{
+    fn value(&self, u: T, v: T, point: Point3<T>) -> RGB<U> {
+        self.as_ref().value(u, v, point)
+    }
+}
+
+/// Same colour everywhere
+#[derive(Clone, Copy)]
+pub struct SolidColor<U>(RGB<U>);
+
+impl<U> SolidColor<U> {
+    pub fn new(color: RGB<U>) -> Self {
+        Self(color)
+    }
+}
+
+impl<T, U> Texture<T, U> for SolidColor<U>
+where
+    U: Float,
+{
+    fn value(&self, _u: T, _v: T, _point: Point3<T>) -> RGB<U> {
+        self.0
+    }
+}
+
+/// Alternates between two textures in a 3D grid of cubes, so it does not depend on the
+/// texture coordinates of the surface it is applied to
+pub struct CheckerTexture<T, Tex> {
+    inv_scale: T,
+    even: Tex,
+    odd: Tex,
+}
+
+impl<T, Tex> CheckerTexture<T, Tex>
+where
+    T: Float,
+{
+    /// `scale` is the side of one cube
+    pub fn new(scale: T, even: Tex, odd: Tex) -> Self {
+        Self {
+            inv_scale: T::one() / scale,
+            even,
+            odd,
+        }
+    }
+}
+
+impl<T, U> CheckerTexture<T, SolidColor<U>>
+where
+    T: Float,
+{
+    pub fn from_colors(scale: T, even: RGB<U>, odd: RGB<U>) -> Self {
+        Self::new(scale, SolidColor::new(even), SolidColor::new(odd))
+    }
+}
+
+impl<T, U, Tex> Texture<T, U> for CheckerTexture<T, Tex>
+where
+    T: Float,
+    Tex: Texture<T, U>,
+{
+    fn value(&self, u: T, v: T, point: Point3<T>) -> RGB<U> {
+        let cell = |x: T| (x * self.inv_scale).floor().to_i64().unwrap_or(0);
+        let sum = cell(point.x) + cell(point.y) + cell(point.z);
+
+        if sum.rem_euclid(2) == 0 {
+            self.even.value(u, v, point)
+        } else {
+            self.odd.value(u, v, point)
+        }
+    }
+}
+
+/// Image mapped onto the surface with its texture coordinates, bilinearly filtered
+pub struct ImageTexture<U> {
+    image: Image<U>,
+    wrap: Wrap,
+}
+
+impl<U> ImageTexture<U> {
+    /// Coordinates outside of [0,1] are clamped to the edges of the image
+    pub fn new(image: Image<U>) -> Self {
+        Self {
+            image,
+            wrap: Wrap::Clamp,
+        }
+    }
+
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+}
+
+impl<T, U> Texture<T, U> for ImageTexture<U>
+where
+    T: Float + AsPrimitive<U>,
+    U: 'static + Float,
+{
+    fn value(&self, u: T, v: T, _point: Point3<T>) -> RGB<U> {
+        self.image.sample(u, v, self.wrap)
+    }
+}
+
+fn noise_at<T: Float, N: Noise>(noise: &N, point: Point3<T>, scale: f64) -> f64 {
+    noise.sample(
+        point.x.to_f64().unwrap() * scale,
+        point.y.to_f64().unwrap() * scale,
+        point.z.to_f64().unwrap() * scale,
+    )
+}
+
+fn mix<U: Float>(a: RGB<U>, b: RGB<U>, t: f64) -> RGB<U> {
+    let t = U::from_f64(t.clamp(0.0, 1.0)).unwrap();
+    a * (U::one() - t) + b * t
+}
+
+/// Veins running across the Z axis, bent by the noise.
+///
+/// Works best with [`noise::Turbulence`] over gradient noise.
+pub struct MarbleTexture<N, U> {
+    noise: N,
+    scale: f64,
+    distortion: f64,
+    light: RGB<U>,
+    dark: RGB<U>,
+}
+
+impl<N, U> MarbleTexture<N, U>
+where
+    U: Float,
+{
+    /// `scale` is the frequency of the veins, white marble with dark grey veins
+    pub fn new(noise: N, scale: f64) -> Self {
+        Self {
+            noise,
+            scale,
+            distortion: 10.0,
+            light: RGB::new(U::constant(0.9), U::constant(0.9), U::constant(0.88)),
+            dark: RGB::new(U::constant(0.15), U::constant(0.15), U::constant(0.17)),
+        }
+    }
+
+    pub fn with_colors(mut self, light: RGB<U>, dark: RGB<U>) -> Self {
+        self.light = light;
+        self.dark = dark;
+        self
+    }
+
+    /// How much the noise bends the veins
+    pub fn with_distortion(mut self, distortion: f64) -> Self {
+        self.distortion = distortion;
+        self
+    }
+}
+
+impl<T, U, N> Texture<T, U> for MarbleTexture<N, U>
+where
+    T: Float,
+    U: Float,
+    N: Noise,
+{
+    fn value(&self, _u: T, _v: T, point: Point3<T>) -> RGB<U> {
+        let z = point.z.to_f64().unwrap();
+        let phase = self.scale * z + self.distortion * noise_at(&self.noise, point, 1.0);
+        // sharpen the veins, they are only dark near the troughs of the wave
+        let t = (0.5 * (1.0 + phase.sin())).powf(0.5);
+        mix(self.dark, self.light, t)
+    }
+}
+
+/// Growth rings around the Y axis, wobbled by the noise
+pub struct WoodTexture<N, U> {
+    noise: N,
+    rings: f64,
+    distortion: f64,
+    light: RGB<U>,
+    dark: RGB<U>,
+}
+
+impl<N, U> WoodTexture<N, U>
+where
+    U: Float,
+{
+    /// `rings` is the number of rings per unit of distance from the axis
+    pub fn new(noise: N, rings: f64) -> Self {
+        Self {
+            noise,
+            rings,
+            distortion: 0.5,
+            light: RGB::new(U::constant(0.72), U::constant(0.5), U::constant(0.28)),
+            dark: RGB::new(U::constant(0.4), U::constant(0.22), U::constant(0.1)),
+        }
+    }
+
+    pub fn with_colors(mut self, light: RGB<U>, dark: RGB<U>) -> Self {
+        self.light = light;
+        self.dark = dark;
+        self
+    }
+
+    /// Displacement of the rings by the noise, in rings
+    pub fn with_distortion(mut self, distortion: f64) -> Self {
+        self.distortion = distortion;
+        self
+    }
+}
+
+impl<T, U, N> Texture<T, U> for WoodTexture<N, U>
+where
+    T: Float,
+    U: Float,
+    N: Noise,
+{
+    fn value(&self, _u: T, _v: T, point: Point3<T>) -> RGB<U> {
+        let (x, z) = (point.x.to_f64().unwrap(), point.z.to_f64().unwrap());
+        let radius = (x * x + z * z).sqrt();
+        let rings = radius * self.rings + self.distortion * noise_at(&self.noise, point, 1.0);
+        // light early wood fading into the dark late wood of each ring
+        let t = rings - rings.floor();
+        mix(self.light, self.dark, t * t)
+    }
+}
+
+/// Colours picked by the value of the noise like altitudes on a map, from deep water to
+/// snow.
+///
+/// Works best with [`noise::Fbm`] or [`noise::Ridged`].
+pub struct TerrainTexture<N, U> {
+    noise: N,
+    scale: f64,
+    /// noise values and their colours, sorted by value
+    ramp: Vec<(f64, RGB<U>)>,
+}
+
+impl<N, U> TerrainTexture<N, U>
+where
+    U: Float,
+{
+    /// `scale` is the frequency of the noise, the default ramp expects values in [-1, 1]
+    pub fn new(noise: N, scale: f64) -> Self {
+        let rgb = |r, g, b| RGB::new(U::constant(r), U::constant(g), U::constant(b));
+        Self {
+            noise,
+            scale,
+            ramp: vec![
+                (-1.0, rgb(0.0, 0.05, 0.25)),
+                (-0.05, rgb(0.05, 0.25, 0.55)),
+                (0.0, rgb(0.76, 0.7, 0.5)),
+                (0.05, rgb(0.2, 0.45, 0.12)),
+                (0.3, rgb(0.15, 0.3, 0.1)),
+                (0.4, rgb(0.4, 0.36, 0.32)),
+                (0.55, rgb(0.95, 0.95, 0.95)),
+            ],
+        }
+    }
+
+    /// Linear blend between colours at the given noise values, values outside the ramp
+    /// take the colour of its ends
+    ///
+    /// # Panics
+    ///
+    /// When the ramp is empty or not sorted by value
+    pub fn with_ramp(mut self, ramp: Vec<(f64, RGB<U>)>) -> Self {
+        assert!(!ramp.is_empty());
+        assert!(ramp.windows(2).all(|w| w[0].0 <= w[1].0));
+        self.ramp = ramp;
+        self
+    }
+}
+
+impl<T, U, N> Texture<T, U> for TerrainTexture<N, U>
+where
+    T: Float,
+    U: Float,
+    N: Noise,
+{
+    fn value(&self, _u: T, _v: T, point: Point3<T>) -> RGB<U> {
+        let height = noise_at(&self.noise, point, self.scale);
+        let above = self.ramp.partition_point(|(value, _)| *value <= height);
+        if above == 0 {
+            return self.ramp[0].1;
+        }
+        if above == self.ramp.len() {
+            return self.ramp[above - 1].1;
+        }
+        let (low, low_color) = self.ramp[above - 1];
+        let (high, high_color) = self.ramp[above];
+        mix(low_color, high_color, (height - low) / (high - low))
+    }
+}
@@ -0,0 +1,226 @@
+use math::{Dither, Float, RGB};
+
+/// Curve compressing linear radiance, which can go far above 1, into the [0, 1] range of
+/// a display
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ToneMapping {
+    /// No curve, everything above 1 clips
+    #[default]
+    None,
+    /// L / (1 + L) on the luminance, never reaches white
+    Reinhard,
+    /// Reinhard with a white point, luminance at `white` and above maps to 1
+    ReinhardExtended { white: f64 },
+    /// John Hable's filmic curve from Uncharted 2, per channel
+    Hable,
+    /// Stephen Hill's fit of the ACES reference rendering and sRGB output transforms
+    Aces,
+}
+
+impl ToneMapping {
+    /// Names accepted by [`ToneMapping::from_name`]
+    pub const NAMES: [&'static str; 5] = ["none", "reinhard", "reinhard-extended", "hable", "aces"];
+
+    /// `reinhard-extended` gets a white point of 4
+    pub fn from_name(name: &str) -> Option<ToneMapping> {
+        match name {
+            "none" => Some(ToneMapping::None),
+            "reinhard" => Some(ToneMapping::Reinhard),
+            "reinhard-extended" => Some(ToneMapping::ReinhardExtended { white: 4.0 }),
+            "hable" => Some(ToneMapping::Hable),
+            "aces" => Some(ToneMapping::Aces),
+            _ => None,
+        }
+    }
+
+    pub fn map<U: Float>(&self, color: RGB<U>) -> RGB<U> {
+        let [r, g, b] = [color.r, color.g, color.b].map(|c| c.to_f64().unwrap().max(0.0));
+        let [r, g, b] = match *self {
+            ToneMapping::None => [r, g, b],
+            ToneMapping::Reinhard => scale_luminance([r, g, b], |l| l / (1.0 + l)),
+            ToneMapping::ReinhardExtended { white } => scale_luminance([r, g, b], |l| {
+                (l * (1.0 + l / (white * white)) / (1.0 + l)).min(1.0)
+            }),
+            ToneMapping::Hable => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE: f64 = 11.2;
+                let white_scale = 1.0 / hable_partial(WHITE);
+                [r, g, b].map(|c| hable_partial(c * EXPOSURE_BIAS) * white_scale)
+            }
+            ToneMapping::Aces => aces_fitted([r, g, b]),
+        };
+        RGB::new(
+            U::from_f64(r).unwrap(),
+            U::from_f64(g).unwrap(),
+            U::from_f64(b).unwrap(),
+        )
+    }
+}
+
+fn luminance([r, g, b]: [f64; 3]) -> f64 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// Applies the curve to the luminance and scales the channels by the same ratio, so the
+/// hue is kept
+fn scale_luminance(color: [f64; 3], curve: impl Fn(f64) -> f64) -> [f64; 3] {
+    let l = luminance(color);
+    if l <= 0.0 {
+        return [0.0; 3];
+    }
+    let scale = curve(l) / l;
+    color.map(|c| c * scale)
+}
+
+fn hable_partial(x: f64) -> f64 {
+    const A: f64 = 0.15; // shoulder strength
+    const B: f64 = 0.50; // linear strength
+    const C: f64 = 0.10; // linear angle
+    const D: f64 = 0.20; // toe strength
+    const E: f64 = 0.02; // toe numerator
+    const F: f64 = 0.30; // toe denominator
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+fn aces_fitted(color: [f64; 3]) -> [f64; 3] {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let multiply =
+        |m: &[[f64; 3]; 3], c: [f64; 3]| m.map(|row| row[0] * c[0] + row[1] * c[1] + row[2] * c[2]);
+
+    let color = multiply(&INPUT, color).map(|v| {
+        let a = v * (v + 0.0245786) - 0.000090537;
+        let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+        a / b
+    });
+    multiply(&OUTPUT, color).map(|v| v.clamp(0.0, 1.0))
+}
+
+/// How linear renders are turned into 8-bit images: exposure, then tone mapping, then
+/// sRGB encoding with optional dithering
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct DisplaySettings {
+    /// In stops, every stop doubles the brightness
+    pub exposure: f64,
+    pub tone_mapping: ToneMapping,
+    pub dither: Dither,
+}
+
+impl DisplaySettings {
+    /// Exposed and tone mapped colours, still linear
+    pub fn tone_map<U: Float>(&self, pixels: &[RGB<U>]) -> Vec<RGB<U>> {
+        let scale = U::from_f64(self.exposure.exp2()).unwrap();
+        pixels
+            .iter()
+            .map(|&pixel| self.tone_mapping.map(pixel * scale))
+            .collect()
+    }
+
+    /// 8-bit sRGB triplets, `width` is the number of pixels in a row
+    pub fn to_srgb_array<U: Float>(&self, pixels: &[RGB<U>], width: u32) -> Vec<u8> {
+        RGB::to_srgb_array_dithered(&self.tone_map(pixels), width, self.dither)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operators() -> Vec<ToneMapping> {
+        ToneMapping::NAMES
+            .iter()
+            .map(|name| ToneMapping::from_name(name).unwrap())
+            .collect()
+    }
+
+    fn grey(operator: ToneMapping, l: f64) -> RGB<f64> {
+        operator.map(RGB::new(l, l, l))
+    }
+
+    #[test]
+    fn black_stays_black() {
+        for operator in operators() {
+            let black = grey(operator, 0.0);
+            assert_eq!([black.r, black.g, black.b], [0.0; 3], "{operator:?}");
+            // negative radiance is treated as black
+            let negative = grey(operator, -1.0);
+            assert_eq!(
+                [negative.r, negative.g, negative.b],
+                [0.0; 3],
+                "{operator:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn curves_are_monotonic() {
+        for operator in operators() {
+            // scaling the channels by curve(l) / l can round a step up by an ulp
+            let tolerance = 1e-12;
+            let mut previous = grey(operator, 0.0);
+            for i in 1..=2000 {
+                let l = (i as f64 * 0.01).powi(2);
+                let mapped = grey(operator, l);
+                for c in 0..3 {
+                    assert!(
+                        mapped[c] >= previous[c] - tolerance,
+                        "{operator:?} decreases at {l}: {} < {}",
+                        mapped[c],
+                        previous[c]
+                    );
+                }
+                previous = mapped;
+            }
+        }
+    }
+
+    #[test]
+    fn curves_are_monotonic_per_channel() {
+        for operator in operators() {
+            let mut previous = operator.map(RGB::new(0.0, 0.5, 0.25));
+            for i in 1..=1000 {
+                let mapped = operator.map(RGB::new(i as f64 * 0.02, 0.5, 0.25));
+                assert!(mapped.r >= previous.r, "{operator:?}");
+                previous = mapped;
+            }
+        }
+    }
+
+    #[test]
+    fn reinhard_extended_maps_its_white_point_to_one() {
+        for white in [1.0, 2.0, 4.0, 11.2] {
+            let operator = ToneMapping::ReinhardExtended { white };
+            let mapped = grey(operator, white);
+            for c in 0..3 {
+                assert!(
+                    (mapped[c] - 1.0).abs() < 1e-12,
+                    "white {white}: {}",
+                    mapped[c]
+                );
+            }
+            let brighter = grey(operator, 2.0 * white);
+            assert_eq!([brighter.r, brighter.g, brighter.b], [1.0; 3]);
+            assert!(grey(operator, 0.5 * white).r < 1.0);
+        }
+    }
+
+    #[test]
+    fn exposure_doubles_per_stop() {
+        let display = DisplaySettings {
+            exposure: 2.0,
+            ..DisplaySettings::default()
+        };
+        let mapped = display.tone_map(&[RGB::new(0.125, 0.25, 0.0)]);
+        assert_eq!([mapped[0].r, mapped[0].g, mapped[0].b], [0.5, 1.0, 0.0]);
+    }
+}
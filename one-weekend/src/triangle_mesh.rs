@@ -0,0 +1,292 @@
+use std::sync::Arc;
+
+use math::Aabb;
+use math::Float;
+use math::Interval;
+use math::Point3;
+use math::Ray;
+use math::Vector3;
+
+use crate::bvh::BvhTree;
+use crate::geometric_primitives::{intersect_triangle, triangle_normal};
+use crate::HitRecord;
+use crate::Hittable;
+use crate::Material;
+
+/// Indices of one triangle into the buffers of its mesh.
+///
+/// Each attribute has its own indices, like in OBJ files, so a vertex can share its
+/// position with its neighbours while having a different normal or texture coordinate.
+#[derive(Clone, Copy)]
+pub struct MeshTriangle {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+impl MeshTriangle {
+    pub fn new(positions: [usize; 3]) -> MeshTriangle {
+        MeshTriangle {
+            positions,
+            normals: None,
+            uvs: None,
+        }
+    }
+}
+
+/// Triangles sharing vertex, normal and texture coordinate buffers.
+///
+/// Triangles with normal indices are smooth shaded, interpolating the vertex normals
+/// across the face. The mesh keeps its own hierarchy over its triangles, so it can sit
+/// in the world like any other primitive.
+pub struct TriangleMesh<T, U> {
+    positions: Vec<Point3<T>>,
+    normals: Vec<Vector3<T>>,
+    uvs: Vec<(T, T)>,
+    triangles: Vec<MeshTriangle>,
+    material: Arc<dyn Material<T, U>>,
+    bvh: BvhTree<T>,
+}
+
+impl<T, U> TriangleMesh<T, U>
+where
+    T: Float,
+{
+    /// # Panics
+    ///
+    /// When a triangle indexes outside of its buffers
+    pub fn new(
+        positions: Vec<Point3<T>>,
+        normals: Vec<Vector3<T>>,
+        uvs: Vec<(T, T)>,
+        triangles: Vec<MeshTriangle>,
+        material: Arc<dyn Material<T, U>>,
+    ) -> TriangleMesh<T, U> {
+        for triangle in &triangles {
+            assert!(triangle.positions.iter().all(|&i| i < positions.len()));
+            assert!(triangle
+                .normals
+                .is_none_or(|n| n.iter().all(|&i| i < normals.len())));
+            assert!(triangle
+                .uvs
+                .is_none_or(|n| n.iter().all(|&i| i < uvs.len())));
+        }
+
+        let bounds: Vec<Aabb<T>> = triangles
+            .iter()
+            .map(|triangle| {
+                Aabb::surrounding_points(triangle.positions.map(|i| positions[i]))
+                    .pad_to_minimums(T::constant(0.0001))
+            })
+            .collect();
+
+        TriangleMesh {
+            positions,
+            normals,
+            uvs,
+            triangles,
+            material,
+            bvh: BvhTree::new(&bounds),
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn hit_triangle(
+        &self,
+        index: usize,
+        ray: &Ray<T>,
+        ray_t: Interval<T>,
+    ) -> Option<HitRecord<'_, T, U>>
+    where
+        U: Float,
+    {
+        let triangle = &self.triangles[index];
+        let vertices = triangle.positions.map(|i| self.positions[i]);
+        let (t, b1, b2) = intersect_triangle(ray, &ray_t, &vertices)?;
+        let b0 = T::one() - b1 - b2;
+
+        let (u, v) = match triangle.uvs {
+            Some([i0, i1, i2]) => {
+                let (uv0, uv1, uv2) = (self.uvs[i0], self.uvs[i1], self.uvs[i2]);
+                (
+                    uv0.0 * b0 + uv1.0 * b1 + uv2.0 * b2,
+                    uv0.1 * b0 + uv1.1 * b1 + uv2.1 * b2,
+                )
+            }
+            None => (b1, b2),
+        };
+
+        let geometric_normal = triangle_normal(&vertices);
+        let mut hit_record = HitRecord::new(
+            ray,
+            ray.at(t),
+            geometric_normal,
+            self.material.as_ref(),
+            t,
+            u,
+            v,
+        );
+
+        if let Some([i0, i1, i2]) = triangle.normals {
+            let mut shading_normal =
+                (self.normals[i0] * b0 + self.normals[i1] * b1 + self.normals[i2] * b2)
+                    .unit_vector();
+            // keep the shading normal on the same side as the face
+            if shading_normal.dot(geometric_normal) < T::constant(0.0) {
+                shading_normal = -shading_normal;
+            }
+            hit_record.normal = if hit_record.front_face {
+                shading_normal
+            } else {
+                -shading_normal
+            };
+        }
+
+        Some(hit_record)
+    }
+}
+
+impl<T, U> Hittable<T, U> for TriangleMesh<T, U>
+where
+    T: Float,
+    U: Float,
+{
+    fn hit(&self, ray: &Ray<T>, ray_t: Interval<T>) -> Option<HitRecord<'_, T, U>> {
+        self.bvh.hit(ray, ray_t, |index, ray_t| {
+            self.hit_triangle(index, ray, ray_t)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb<T> {
+        self.bvh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noise::RandomNumberGenerator;
+
+    use super::*;
+    use crate::{GeometricPrimitive, Lambertian, Triangle};
+
+    fn material() -> Arc<dyn Material<f64, f32>> {
+        Arc::new(Lambertian::new(math::RGB::new(0.5, 0.5, 0.5)))
+    }
+
+    fn quad_positions() -> Vec<Point3<f64>> {
+        vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ]
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_position_index_out_of_range() {
+        TriangleMesh::new(
+            quad_positions(),
+            Vec::new(),
+            Vec::new(),
+            vec![MeshTriangle::new([0, 1, 4])],
+            material(),
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_normal_index_out_of_range() {
+        let mut triangle = MeshTriangle::new([0, 1, 2]);
+        triangle.normals = Some([0, 0, 1]);
+        TriangleMesh::new(
+            quad_positions(),
+            vec![Vector3::new(0.0, 0.0, 1.0)],
+            Vec::new(),
+            vec![triangle],
+            material(),
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_uv_index_out_of_range() {
+        let mut triangle = MeshTriangle::new([0, 1, 2]);
+        triangle.uvs = Some([0, 1, 3]);
+        TriangleMesh::new(
+            quad_positions(),
+            Vec::new(),
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+            vec![triangle],
+            material(),
+        );
+    }
+
+    #[test]
+    fn accepts_indices_in_range() {
+        let mut triangle = MeshTriangle::new([0, 2, 3]);
+        triangle.normals = Some([0, 0, 0]);
+        triangle.uvs = Some([0, 1, 2]);
+        let mesh = TriangleMesh::new(
+            quad_positions(),
+            vec![Vector3::new(0.0, 0.0, 1.0)],
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+            vec![MeshTriangle::new([0, 1, 2]), triangle],
+            material(),
+        );
+        assert_eq!(mesh.triangle_count(), 2);
+    }
+
+    #[test]
+    fn hits_match_a_brute_force_scan() {
+        let mut rng = RandomNumberGenerator::new(7);
+        let random_point = |rng: &mut RandomNumberGenerator, extent: f64| {
+            Point3::new(
+                rng.next_range_f64(-extent, extent),
+                rng.next_range_f64(-extent, extent),
+                rng.next_range_f64(-extent, extent),
+            )
+        };
+        let positions: Vec<Point3<f64>> = (0..200).map(|_| random_point(&mut rng, 5.0)).collect();
+        let triangles: Vec<MeshTriangle> = (0..400)
+            .map(|_| {
+                MeshTriangle::new(
+                    [0; 3].map(|_| rng.next_i32().unsigned_abs() as usize % positions.len()),
+                )
+            })
+            .collect();
+        let brute_force: Vec<GeometricPrimitive<f64, f32>> = triangles
+            .iter()
+            .map(|triangle| {
+                let [p0, p1, p2] = triangle.positions.map(|i| positions[i]);
+                GeometricPrimitive::Triangle(Triangle::new(p0, p1, p2, material()))
+            })
+            .collect();
+        let brute_force = brute_force.as_slice();
+        let mesh = TriangleMesh::new(positions, Vec::new(), Vec::new(), triangles, material());
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = random_point(&mut rng, 8.0);
+            let target = random_point(&mut rng, 4.0);
+            let ray = Ray::new(origin, target - origin);
+            let ray_t = Interval::new(0.001, f64::INFINITY);
+            let expected = Hittable::<f64, f32>::hit(&brute_force, &ray, ray_t);
+            let actual = mesh.hit(&ray, ray_t);
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(e), Some(a)) => {
+                    assert_eq!(e.t, a.t);
+                    assert_eq!((e.u, e.v), (a.u, a.v));
+                    assert_eq!(e.front_face, a.front_face);
+                    hits += 1;
+                }
+                (e, a) => panic!("brute force hit {}, mesh hit {}", e.is_some(), a.is_some()),
+            }
+        }
+        assert!(hits > 100);
+    }
+}